@@ -0,0 +1,197 @@
+use crate::bindings::theater::simple::environment;
+use crate::bindings::theater::simple::http_client::{send_http, HttpRequest};
+use crate::bindings::theater::simple::runtime::log;
+use crate::types::gemini::GeminiError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::sha2::Sha256;
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable holding the service-account JSON key
+pub const SERVICE_ACCOUNT_KEY_VAR: &str = "GOOGLE_SERVICE_ACCOUNT_KEY";
+
+/// OAuth2 scope needed to call Vertex AI
+pub const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// Lifetime requested for each signed assertion, in seconds
+const ASSERTION_LIFETIME_SECS: u64 = 3600;
+
+/// Tokens this close to expiry are refreshed rather than reused
+const EXPIRY_MARGIN_MS: u64 = 60_000;
+
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+/// The fields of a Google service-account JSON key that token minting needs
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceAccountKey {
+    /// Service account email, used as the assertion issuer
+    pub client_email: String,
+
+    /// PKCS#8 PEM-encoded RSA private key
+    pub private_key: String,
+
+    /// Id of the key, sent as the JWT `kid`
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+
+    /// Where assertions are exchanged for access tokens
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+impl std::fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("private_key", &"<redacted>")
+            .field("private_key_id", &self.private_key_id)
+            .field("token_uri", &self.token_uri)
+            .finish()
+    }
+}
+
+/// Read the service-account key from the environment.
+///
+/// The key is read again whenever a token is minted, so the private key never
+/// becomes part of the actor state.
+pub fn service_account_from_environment() -> Result<ServiceAccountKey, String> {
+    let json = environment::get_var(SERVICE_ACCOUNT_KEY_VAR)
+        .ok_or_else(|| "Service account key not found in environment".to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse service account key: {}", e))
+}
+
+/// An OAuth2 access token and when it stops being valid
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccessToken {
+    pub token: String,
+    /// Expiry in milliseconds on the Theater clock
+    pub expires_at: u64,
+}
+
+impl AccessToken {
+    /// Whether the token can still be used at `now`, leaving a margin for the request
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now + EXPIRY_MARGIN_MS < self.expires_at
+    }
+}
+
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+#[derive(Serialize)]
+struct JwtClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Build the RS256-signed JWT assertion for the OAuth2 JWT bearer grant
+pub fn sign_assertion(
+    key: &ServiceAccountKey,
+    audience: &str,
+    scope: &str,
+    now_secs: u64,
+) -> Result<String, GeminiError> {
+    let mut header = serde_json::json!({ "alg": "RS256", "typ": "JWT" });
+    if let Some(kid) = &key.private_key_id {
+        header["kid"] = serde_json::Value::String(kid.clone());
+    }
+
+    let claims = JwtClaims {
+        iss: &key.client_email,
+        scope,
+        aud: audience,
+        iat: now_secs,
+        exp: now_secs + ASSERTION_LIFETIME_SECS,
+    };
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+    );
+
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&key.private_key).map_err(|e| {
+        GeminiError::InvalidRequest(format!("Invalid service account private key: {}", e))
+    })?;
+    let signature = SigningKey::<Sha256>::new(private_key).sign(signing_input.as_bytes());
+
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature.to_bytes())
+    ))
+}
+
+/// Exchange a freshly signed assertion for an access token.
+///
+/// `token_uri` overrides the one in the key, for example to point at a local
+/// stand-in; it is also used as the assertion audience.
+pub fn fetch_access_token(
+    key: &ServiceAccountKey,
+    token_uri: Option<&str>,
+    now_ms: u64,
+) -> Result<AccessToken, GeminiError> {
+    let token_uri = token_uri.unwrap_or(&key.token_uri);
+    log(&format!(
+        "Requesting access token for {} from {}",
+        key.client_email, token_uri
+    ));
+
+    let assertion = sign_assertion(key, token_uri, CLOUD_PLATFORM_SCOPE, now_ms / 1000)?;
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("grant_type", JWT_BEARER_GRANT)
+        .append_pair("assertion", &assertion)
+        .finish();
+
+    let request = HttpRequest {
+        method: "POST".to_string(),
+        uri: token_uri.to_string(),
+        headers: vec![(
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        )],
+        body: Some(body.into_bytes()),
+    };
+
+    let response = send_http(&request).map_err(GeminiError::HttpError)?;
+    let body = response.body.unwrap_or_default();
+
+    if response.status != 200 {
+        return Err(GeminiError::ApiError {
+            status: response.status,
+            message: format!(
+                "Token exchange failed: {}",
+                String::from_utf8_lossy(&body)
+            ),
+        });
+    }
+
+    let token: TokenResponse = serde_json::from_slice(&body)?;
+    Ok(AccessToken {
+        token: token.access_token,
+        expires_at: now_ms + token.expires_in * 1000,
+    })
+}
@@ -0,0 +1,102 @@
+use crate::api::gemini::check_status;
+use crate::api::GeminiClient;
+use crate::bindings::theater::simple::http_client::HttpRequest;
+use crate::bindings::theater::simple::runtime::log;
+use crate::types::cached_content::{CachedContent, ListCachedContentsResponse};
+use crate::types::gemini::GeminiError;
+
+impl GeminiClient {
+    /// Create a cache for `content`, whose `model` names the model it is for
+    pub fn create_cached_content(
+        &self,
+        mut content: CachedContent,
+    ) -> Result<CachedContent, GeminiError> {
+        let model = content.model.as_deref().ok_or_else(|| {
+            GeminiError::InvalidRequest("A cached content needs a model".to_string())
+        })?;
+        let model = model.strip_prefix("models/").unwrap_or(model);
+        content.model = Some(self.model_resource(model));
+
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "POST".to_string(),
+            uri: self.url(&self.cached_contents_path(), &[]),
+            headers: self.headers(),
+            body: Some(serde_json::to_vec(&content)?),
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        let created: CachedContent = serde_json::from_slice(&body)?;
+        log(&format!(
+            "Created cached content {}",
+            created.name.as_deref().unwrap_or_default()
+        ));
+        Ok(created)
+    }
+
+    /// Fetch a cache's metadata
+    pub fn get_cached_content(&self, name: &str) -> Result<CachedContent, GeminiError> {
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "GET".to_string(),
+            uri: self.url(&self.cached_content_resource(name), &[]),
+            headers: self.headers(),
+            body: None,
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// List one page of the project's caches
+    pub fn list_cached_contents(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<ListCachedContentsResponse, GeminiError> {
+        let page_size = page_size.map(|size| size.to_string());
+        let mut query = vec![];
+        if let Some(size) = &page_size {
+            query.push(("pageSize", size.as_str()));
+        }
+        if let Some(token) = page_token {
+            query.push(("pageToken", token));
+        }
+
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "GET".to_string(),
+            uri: self.url(&self.cached_contents_path(), &query),
+            headers: self.headers(),
+            body: None,
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Give a cache a new time to live, counted from now
+    pub fn update_cached_content_ttl(
+        &self,
+        name: &str,
+        ttl_secs: u64,
+    ) -> Result<CachedContent, GeminiError> {
+        let body = serde_json::json!({ "ttl": format!("{}s", ttl_secs) });
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "PATCH".to_string(),
+            uri: self.url(
+                &self.cached_content_resource(name),
+                &[("updateMask", "ttl")],
+            ),
+            headers: self.headers(),
+            body: Some(serde_json::to_vec(&body)?),
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Delete a cache before it expires
+    pub fn delete_cached_content(&self, name: &str) -> Result<(), GeminiError> {
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "DELETE".to_string(),
+            uri: self.url(&self.cached_content_resource(name), &[]),
+            headers: self.headers(),
+            body: None,
+        })?;
+        check_status(response).map(|_| ())
+    }
+}
@@ -0,0 +1,174 @@
+use crate::api::gemini::check_status;
+use crate::api::GeminiClient;
+use crate::bindings::theater::simple::http_client::HttpRequest;
+use crate::bindings::theater::simple::runtime::log;
+use crate::types::files::{file_name, File, FileState, ListFilesResponse, UploadFileResponse};
+use crate::types::gemini::GeminiError;
+
+/// Size of each resumable upload chunk; the API wants multiples of 256 KiB
+const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// How long to wait between checks on a file that is still processing
+const FILE_POLL_INTERVAL_MS: u32 = 2_000;
+
+impl GeminiClient {
+    /// Upload a file with the resumable protocol: start a session, send the bytes
+    /// in chunks, and finalize with the last one
+    pub fn upload_file(
+        &self,
+        data: &[u8],
+        mime_type: &str,
+        display_name: Option<&str>,
+    ) -> Result<File, GeminiError> {
+        self.require_files_api()?;
+
+        let mut headers = self.headers();
+        headers.extend([
+            ("X-Goog-Upload-Protocol".to_string(), "resumable".to_string()),
+            ("X-Goog-Upload-Command".to_string(), "start".to_string()),
+            ("X-Goog-Upload-Header-Content-Length".to_string(), data.len().to_string()),
+            ("X-Goog-Upload-Header-Content-Type".to_string(), mime_type.to_string()),
+        ]);
+        let body = serde_json::json!({ "file": { "displayName": display_name } });
+
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "POST".to_string(),
+            uri: self.upload_url("files"),
+            headers,
+            body: Some(serde_json::to_vec(&body)?),
+        })?;
+        let response = check_status(response)?;
+        let session_url = response
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("x-goog-upload-url"))
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| GeminiError::InvalidResponse("No upload URL in response".to_string()))?;
+
+        log(&format!("Uploading {} bytes of {}", data.len(), mime_type));
+
+        // An empty file still needs one finalizing request
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            data.chunks(UPLOAD_CHUNK_BYTES).collect()
+        };
+        let mut offset = 0;
+        let mut last_response = None;
+        for (index, chunk) in chunks.iter().enumerate() {
+            let command = if index + 1 == chunks.len() {
+                "upload, finalize"
+            } else {
+                "upload"
+            };
+
+            let mut headers: Vec<(String, String)> = self
+                .headers()
+                .into_iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("content-type"))
+                .collect();
+            headers.extend([
+                ("X-Goog-Upload-Command".to_string(), command.to_string()),
+                ("X-Goog-Upload-Offset".to_string(), offset.to_string()),
+            ]);
+
+            let response = self.make_request_with_retry(&HttpRequest {
+                method: "POST".to_string(),
+                uri: session_url.clone(),
+                headers,
+                body: Some(chunk.to_vec()),
+            })?;
+            last_response = Some(check_status(response)?);
+            offset += chunk.len();
+        }
+
+        let body = last_response.and_then(|r| r.body).unwrap_or_default();
+        let uploaded: UploadFileResponse = serde_json::from_slice(&body)?;
+        log(&format!("Uploaded {}", uploaded.file.name));
+        Ok(uploaded.file)
+    }
+
+    /// Fetch a file's metadata
+    pub fn get_file(&self, name: &str) -> Result<File, GeminiError> {
+        self.require_files_api()?;
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "GET".to_string(),
+            uri: self.url(&file_name(name), &[]),
+            headers: self.headers(),
+            body: None,
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// List one page of the project's files
+    pub fn list_files(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<ListFilesResponse, GeminiError> {
+        self.require_files_api()?;
+        let page_size = page_size.map(|size| size.to_string());
+        let mut query = vec![];
+        if let Some(size) = &page_size {
+            query.push(("pageSize", size.as_str()));
+        }
+        if let Some(token) = page_token {
+            query.push(("pageToken", token));
+        }
+
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "GET".to_string(),
+            uri: self.url("files", &query),
+            headers: self.headers(),
+            body: None,
+        })?;
+        let body = check_status(response)?.body.unwrap_or_default();
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Delete a file before it expires
+    pub fn delete_file(&self, name: &str) -> Result<(), GeminiError> {
+        self.require_files_api()?;
+        let response = self.make_request_with_retry(&HttpRequest {
+            method: "DELETE".to_string(),
+            uri: self.url(&file_name(name), &[]),
+            headers: self.headers(),
+            body: None,
+        })?;
+        check_status(response).map(|_| ())
+    }
+
+    /// Poll a file until it is ACTIVE, failing if processing fails or the deadline
+    /// would pass first
+    pub fn wait_for_file(&self, name: &str) -> Result<File, GeminiError> {
+        loop {
+            let file = self.get_file(name)?;
+            match file.state {
+                FileState::Active => return Ok(file),
+                FileState::Failed => {
+                    return Err(GeminiError::InvalidResponse(format!(
+                        "File {} failed processing: {}",
+                        file.name,
+                        file.error.map(|e| e.to_string()).unwrap_or_default()
+                    )))
+                }
+                _ => {
+                    log(&format!("File {} is still processing", file.name));
+                    self.check_deadline(FILE_POLL_INTERVAL_MS)?;
+                    self.sleep_ms(FILE_POLL_INTERVAL_MS);
+                }
+            }
+        }
+    }
+
+    fn require_files_api(&self) -> Result<(), GeminiError> {
+        if self.is_vertex() {
+            return Err(GeminiError::UnsupportedFeature(
+                "Vertex AI has no Files API; reference files in Cloud Storage by gs:// URI"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
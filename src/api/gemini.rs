@@ -1,8 +1,25 @@
-use crate::bindings::theater::simple::http_client::{send_http, HttpRequest};
+use crate::bindings::theater::simple::http_client::{send_http, HttpRequest, HttpResponse};
 use crate::bindings::theater::simple::runtime::log;
+use crate::bindings::theater::simple::timing;
+use crate::logging::{log_payload, PayloadLogging};
+use crate::media;
 use crate::types::gemini::{
-    GeminiError, GenerateContentRequest, GenerateContentResponse, ModelInfo,
+    BatchEmbedContentsRequest, BatchEmbedContentsResponse, Content, CountTokensRequest,
+    CountTokensResponse, EmbedContentRequest, EmbedContentResponse, GeminiError,
+    GenerateContentRequest, GenerateContentResponse, ListModelsResponse, ModelInfo, Part,
+    PartData, Role, SafetySetting, TaskType, merge_safety_settings,
 };
+use crate::api::keys::{KeyPool, DEFAULT_KEY_COOLDOWN_MS};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Maximum number of texts the API accepts in one `batchEmbedContents` call
+const MAX_EMBED_BATCH_SIZE: usize = 100;
+
+/// Longest single sleep the timing handler allows, its `max_sleep_duration` in the
+/// manifest. Longer waits are slept in pieces.
+pub const MAX_SLEEP_MS: u32 = 60_000;
 
 /// Configuration for retry logic
 #[derive(Clone)]
@@ -28,51 +45,313 @@ impl Default for RetryConfig {
     }
 }
 
+/// How requests are authenticated
+#[derive(Clone)]
+pub enum Credentials {
+    /// Gemini Developer API keys, switched when one runs out of quota
+    ApiKeys(RefCell<KeyPool>),
+    /// An OAuth2 access token, as Vertex AI requires
+    AccessToken(String),
+}
+
 /// Client for interacting with the Google Gemini API
 pub struct GeminiClient {
-    /// Google API key
-    api_key: String,
+    /// Credentials sent with every request
+    credentials: Credentials,
+
+    /// Scheme and host the API is served from
+    root_url: String,
 
-    /// Base URL for the API
-    base_url: String,
+    /// API version path segment, such as "v1beta"
+    api_version: String,
+
+    /// Static headers added to every request
+    extra_headers: BTreeMap<String, String>,
+
+    /// How much of each response body is logged
+    payload_logging: PayloadLogging,
+
+    /// Resource path that model names are appended to
+    models_path: String,
 
     /// Retry configuration
     retry_config: RetryConfig,
+
+    /// Safety settings applied to every generate request unless it overrides them
+    safety_settings: Vec<SafetySetting>,
+
+    /// Overall deadline for everything this client sends, retries included
+    deadline: Option<Deadline>,
+}
+
+/// An end-to-end time budget, measured in milliseconds on the Theater clock
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    /// When the budget started
+    pub started_at: u64,
+    /// Length of the budget
+    pub timeout_ms: u32,
+}
+
+impl Deadline {
+    /// Whether the budget has run out at `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.started_at + self.timeout_ms as u64
+    }
+
+    /// Whether waiting `wait_ms` from `now` still leaves time for another attempt
+    pub fn allows_wait(&self, now: u64, wait_ms: u32) -> bool {
+        !self.is_expired(now + wait_ms as u64)
+    }
 }
 
 impl GeminiClient {
-    /// Create a new Gemini client with default retry configuration
-    pub fn new(api_key: String) -> Self {
+    /// Create a new Gemini client with custom retry configuration, using the key
+    /// the pool currently points at
+    pub fn new_with_retry_config(api_keys: KeyPool, retry_config: RetryConfig) -> Self {
         Self {
-            api_key,
-            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
-            retry_config: RetryConfig::default(),
+            credentials: Credentials::ApiKeys(RefCell::new(api_keys)),
+            root_url: "https://generativelanguage.googleapis.com".to_string(),
+            api_version: "v1beta".to_string(),
+            extra_headers: BTreeMap::new(),
+            payload_logging: PayloadLogging::default(),
+            models_path: "models".to_string(),
+            retry_config,
+            safety_settings: vec![],
+            deadline: None,
         }
     }
 
-    /// Create a new Gemini client with custom retry configuration
-    pub fn new_with_retry_config(api_key: String, retry_config: RetryConfig) -> Self {
+    /// Create a client for Gemini models on Vertex AI in `project` and `location`
+    pub fn new_vertex(
+        project: &str,
+        location: &str,
+        access_token: String,
+        retry_config: RetryConfig,
+    ) -> Self {
+        // The global location has no regional endpoint
+        let host = if location == "global" {
+            "aiplatform.googleapis.com".to_string()
+        } else {
+            format!("{}-aiplatform.googleapis.com", location)
+        };
+
         Self {
-            api_key,
-            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            credentials: Credentials::AccessToken(access_token),
+            root_url: format!("https://{}", host),
+            api_version: "v1".to_string(),
+            extra_headers: BTreeMap::new(),
+            payload_logging: PayloadLogging::default(),
+            models_path: format!(
+                "projects/{}/locations/{}/publishers/google/models",
+                project, location
+            ),
             retry_config,
+            safety_settings: vec![],
+            deadline: None,
+        }
+    }
+
+    /// Send requests to `root_url`, such as a gateway or a local fake server, instead
+    /// of the backend's own host
+    pub fn with_root_url(mut self, root_url: &str) -> Self {
+        self.root_url = root_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Use `api_version` instead of the backend's default
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = api_version.to_string();
+        self
+    }
+
+    /// Add static headers to every request
+    pub fn with_headers(mut self, headers: BTreeMap<String, String>) -> Self {
+        self.extra_headers = headers;
+        self
+    }
+
+    /// Set how much of each response body is logged
+    pub fn with_payload_logging(mut self, payload_logging: PayloadLogging) -> Self {
+        self.payload_logging = payload_logging;
+        self
+    }
+
+    pub(crate) fn is_vertex(&self) -> bool {
+        matches!(self.credentials, Credentials::AccessToken(_))
+    }
+
+    /// Build the URL for `path` with the given query parameters.
+    ///
+    /// Credentials never go into the URL, which ends up in the Theater event chain.
+    pub(crate) fn url(&self, path: &str, query: &[(&str, &str)]) -> String {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        serializer.extend_pairs(query);
+
+        let base_url = format!("{}/{}", self.root_url, self.api_version);
+        let query = serializer.finish();
+        if query.is_empty() {
+            format!("{}/{}", base_url, path)
+        } else {
+            format!("{}/{}?{}", base_url, path, query)
+        }
+    }
+
+    /// Build the URL for `path` on the media upload endpoint
+    pub(crate) fn upload_url(&self, path: &str) -> String {
+        format!("{}/upload/{}/{}", self.root_url, self.api_version, path)
+    }
+
+    /// Collection of cached contents: next to the models on Vertex AI, and at the
+    /// top level on the Gemini Developer API
+    pub(crate) fn cached_contents_path(&self) -> String {
+        match self.models_path.strip_suffix("/publishers/google/models") {
+            Some(location) => format!("{}/cachedContents", location),
+            None => "cachedContents".to_string(),
+        }
+    }
+
+    /// Resource name of a cached content given by id or full name
+    pub(crate) fn cached_content_resource(&self, name: &str) -> String {
+        if name.contains('/') {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.cached_contents_path(), name)
+        }
+    }
+
+    /// Headers for a JSON request: the configured static headers plus the credentials
+    pub(crate) fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(
+            self.extra_headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        self.authorize(&mut headers);
+        headers
+    }
+
+    /// Set the header carrying the current credentials, replacing any earlier value.
+    ///
+    /// Only that header is touched, so a static `Authorization` header for a gateway
+    /// still reaches it when API keys are used.
+    fn authorize(&self, headers: &mut Vec<(String, String)>) {
+        let (name, value) = match &self.credentials {
+            Credentials::ApiKeys(pool) => (
+                "x-goog-api-key",
+                pool.borrow().current_key().map(str::to_string),
+            ),
+            Credentials::AccessToken(token) => {
+                ("Authorization", Some(format!("Bearer {}", token)))
+            }
+        };
+        headers.retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        if let Some(value) = value {
+            headers.push((name.to_string(), value));
+        }
+    }
+
+    /// The API key pool with any cooldowns this client recorded, to be kept for later
+    /// requests
+    pub fn key_pool(&self) -> Option<KeyPool> {
+        match &self.credentials {
+            Credentials::ApiKeys(pool) => Some(pool.borrow().clone()),
+            Credentials::AccessToken(_) => None,
+        }
+    }
+
+    /// Number of API keys this client can switch between
+    fn key_count(&self) -> usize {
+        match &self.credentials {
+            Credentials::ApiKeys(pool) => pool.borrow().keys.len(),
+            Credentials::AccessToken(_) => 0,
+        }
+    }
+
+    /// After a quota error, rest the key in use and switch to another one.
+    ///
+    /// Returns false when there is no other key to switch to.
+    fn switch_key(&self, response: &HttpResponse) -> bool {
+        let Credentials::ApiKeys(pool) = &self.credentials else {
+            return false;
+        };
+
+        let now = timing::now();
+        let cooldown = server_retry_delay_ms(response)
+            .map(u64::from)
+            .unwrap_or(DEFAULT_KEY_COOLDOWN_MS);
+        pool.borrow_mut().cool_down_and_switch(now, now + cooldown)
+    }
+
+    /// Full resource name of `model`
+    pub(crate) fn model_resource(&self, model: &str) -> String {
+        format!("{}/{}", self.models_path, model)
+    }
+
+    /// Bound every request made through this client, retries and waits included,
+    /// by `timeout_ms` from now
+    pub fn with_timeout(mut self, timeout_ms: u32) -> Self {
+        self.deadline = Some(Deadline {
+            started_at: timing::now(),
+            timeout_ms,
+        });
+        self
+    }
+
+    /// Set the default safety settings for generate requests
+    pub fn with_safety_settings(mut self, safety_settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = safety_settings;
+        self
+    }
+
+    /// Wait `wait_ms` if that still leaves time before the deadline, returning
+    /// whether it waited
+    pub fn wait_within_deadline(&self, wait_ms: u32) -> bool {
+        let allowed = self
+            .deadline
+            .is_none_or(|deadline| deadline.allows_wait(timing::now(), wait_ms));
+        allowed && self.sleep_ms(wait_ms)
+    }
+
+    /// Block until the delay has elapsed, using the Theater timing handler, and
+    /// return whether the whole delay was slept
+    pub(crate) fn sleep_ms(&self, ms: u32) -> bool {
+        log(&format!("Waiting {} milliseconds...", ms));
+        for chunk in sleep_chunks(ms) {
+            if let Err(e) = timing::sleep(chunk as u64) {
+                log(&format!("Sleep failed, continuing without waiting: {}", e));
+                return false;
+            }
         }
+        true
     }
 
-    /// Simple sleep implementation using busy waiting
-    /// Note: This is not ideal but works in the WASM environment
-    fn sleep_ms(&self, ms: u32) {
-        log(&format!("Waiting {} milliseconds before retry...", ms));
-        // In a real implementation, we'd use a proper async sleep
-        // For now, we'll just log the delay and continue
-        // The actual delay would need to be implemented based on the runtime capabilities
+    /// Calculate the delay before the next attempt.
+    ///
+    /// A delay requested by the server wins over exponential backoff; either way
+    /// the result is capped by `max_delay_ms`.
+    fn calculate_delay(&self, attempt: u32, response: Option<&HttpResponse>) -> u32 {
+        match response.and_then(server_retry_delay_ms) {
+            Some(delay) => delay.min(self.retry_config.max_delay_ms),
+            None => backoff_delay(&self.retry_config, attempt, timing::now()),
+        }
     }
 
-    /// Calculate delay for exponential backoff
-    fn calculate_delay(&self, attempt: u32) -> u32 {
-        let delay = (self.retry_config.base_delay_ms as f32 
-            * self.retry_config.backoff_multiplier.powi(attempt as i32)) as u32;
-        delay.min(self.retry_config.max_delay_ms)
+    /// Fail with a timeout if waiting `wait_ms` and trying again would overrun the deadline
+    pub(crate) fn check_deadline(&self, wait_ms: u32) -> Result<(), GeminiError> {
+        match self.deadline {
+            Some(deadline) if !deadline.allows_wait(timing::now(), wait_ms) => {
+                log(&format!(
+                    "Request deadline of {}ms would be exceeded. Giving up.",
+                    deadline.timeout_ms
+                ));
+                Err(GeminiError::Timeout {
+                    timeout_ms: deadline.timeout_ms,
+                })
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Check if an HTTP status code is retryable
@@ -87,22 +366,34 @@ impl GeminiClient {
         }
     }
 
-    /// Make HTTP request with retry logic
-    fn make_request_with_retry(&self, request: &HttpRequest) -> Result<crate::bindings::theater::simple::http_client::HttpResponse, GeminiError> {
-        let mut last_error = None;
-        
-        for attempt in 0..=self.retry_config.max_retries {
+    /// Make HTTP request with retry logic.
+    ///
+    /// A quota error (429) first moves the request to another API key without waiting;
+    /// only when no key is left, or the request has switched keys as many times as
+    /// there are keys, does it count as a retry and back off.
+    pub(crate) fn make_request_with_retry(&self, request: &HttpRequest) -> Result<HttpResponse, GeminiError> {
+        let mut attempt = 0;
+        let mut key_switches = 0;
+
+        loop {
+            self.check_deadline(0)?;
+
             log(&format!("Making request attempt {} of {}", attempt + 1, self.retry_config.max_retries + 1));
-            
+
+            // The key may have changed since the request was built
+            let mut request = request.clone();
+            self.authorize(&mut request.headers);
+
             // Make the request
-            let response = match send_http(request) {
+            let response = match send_http(&request) {
                 Ok(resp) => resp,
                 Err(e) => {
-                    last_error = Some(GeminiError::HttpError(e.clone()));
                     if attempt < self.retry_config.max_retries {
-                        let delay = self.calculate_delay(attempt);
+                        let delay = self.calculate_delay(attempt, None);
+                        self.check_deadline(delay)?;
                         log(&format!("HTTP request failed: {}. Retrying in {}ms...", e, delay));
                         self.sleep_ms(delay);
+                        attempt += 1;
                         continue;
                     } else {
                         return Err(GeminiError::HttpError(e));
@@ -113,13 +404,19 @@ impl GeminiClient {
             // Check if we should retry based on status code
             if self.is_retryable_status(response.status) {
                 let message = String::from_utf8_lossy(&response.body.clone().unwrap_or_default()).to_string();
-                last_error = Some(GeminiError::ApiError {
-                    status: response.status,
-                    message: message.clone(),
-                });
+
+                if response.status == 429
+                    && key_switches < self.key_count()
+                    && self.switch_key(&response)
+                {
+                    log("API key ran out of quota. Retrying with the next key...");
+                    key_switches += 1;
+                    continue;
+                }
 
                 if attempt < self.retry_config.max_retries {
-                    let delay = self.calculate_delay(attempt);
+                    let delay = self.calculate_delay(attempt, Some(&response));
+                    self.check_deadline(delay)?;
                     log(&format!(
                         "Received retryable error {} ({}). Retrying in {}ms... (attempt {}/{})",
                         response.status,
@@ -129,6 +426,7 @@ impl GeminiClient {
                         self.retry_config.max_retries + 1
                     ));
                     self.sleep_ms(delay);
+                    attempt += 1;
                     continue;
                 } else {
                     log(&format!(
@@ -146,56 +444,84 @@ impl GeminiClient {
             // Success case or non-retryable error
             return Ok(response);
         }
-
-        // This should never be reached, but return the last error if it happens
-        Err(last_error.unwrap_or(GeminiError::HttpError("Unknown error".to_string())))
     }
 
-    /// List available models from the Gemini API
+    /// List available models from the Gemini API.
+    ///
+    /// Follows `nextPageToken` until every page has been read. If the endpoint cannot
+    /// be reached the built-in model list is returned instead; errors returned by the
+    /// API, such as bad credentials, are passed on. Vertex AI has no listing endpoint
+    /// and always gets the built-in list.
     pub fn list_models(&self) -> Result<Vec<ModelInfo>, GeminiError> {
         log("Listing available Gemini models");
 
-        // In a production environment, we would make a call to the models endpoint
-        // For now, return hardcoded model information
-        Ok(ModelInfo::get_default_models())
+        if self.is_vertex() {
+            log("Vertex AI has no Gemini model listing endpoint, using built-in model list");
+            return Ok(ModelInfo::get_default_models());
+        }
 
-        // Example of how to make the API call (not implemented in this version):
-        /*
-        let url = format!("{}/models?key={}", self.base_url, self.api_key);
+        match self.fetch_models() {
+            Ok(models) => {
+                log(&format!("Found {} models", models.len()));
+                Ok(models)
+            }
+            Err(GeminiError::HttpError(e)) => {
+                log(&format!(
+                    "Models endpoint unreachable: {}. Falling back to built-in model list",
+                    e
+                ));
+                Ok(ModelInfo::get_default_models())
+            }
+            Err(e) => Err(e),
+        }
+    }
 
-        let request = HttpRequest {
-            method: "GET".to_string(),
-            uri: url,
-            headers: vec![
-                ("Content-Type".to_string(), "application/json".to_string()),
-            ],
-            body: None,
-        };
+    /// Fetch every page of the `GET /models` endpoint
+    fn fetch_models(&self) -> Result<Vec<ModelInfo>, GeminiError> {
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
 
-        // Send the request with retry logic
-        let response = self.make_request_with_retry(&request)?;
+        loop {
+            let mut query = vec![("pageSize", "1000")];
+            if let Some(token) = &page_token {
+                query.push(("pageToken", token));
+            }
 
-        // Check status code
-        if response.status != 200 {
-            let message = String::from_utf8_lossy(&response.body.unwrap_or_default()).to_string();
-            return Err(GeminiError::ApiError {
-                status: response.status,
-                message,
-            });
-        }
+            let request = HttpRequest {
+                method: "GET".to_string(),
+                uri: self.url("models", &query),
+                headers: self.headers(),
+                body: None,
+            };
 
-        // Parse the response
-        let body = response.body.ok_or_else(|| {
-            GeminiError::InvalidResponse("No response body".to_string())
-        })?;
+            // Send the request with retry logic
+            let response = self.make_request_with_retry(&request)?;
 
-        log(&format!(
-            "Models API response: {}",
-            String::from_utf8_lossy(&body)
-        ));
+            // Check status code
+            if response.status != 200 {
+                let message =
+                    String::from_utf8_lossy(&response.body.unwrap_or_default()).to_string();
+                return Err(GeminiError::ApiError {
+                    status: response.status,
+                    message,
+                });
+            }
+
+            // Parse the response
+            let body = response
+                .body
+                .ok_or_else(|| GeminiError::InvalidResponse("No response body".to_string()))?;
+            let page: ListModelsResponse = serde_json::from_slice(&body)?;
+
+            models.extend(page.models.into_iter().map(ModelInfo::from));
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
 
-        // Implement response parsing
-        */
+        Ok(models)
     }
 
     /// Generate content using the Gemini API with retry logic
@@ -203,11 +529,140 @@ impl GeminiClient {
         &self,
         request: GenerateContentRequest,
     ) -> Result<GenerateContentResponse, GeminiError> {
-        // Determine the endpoint based on streaming or not
-        let endpoint = "generateContent";
-
         log(&format!("Generating content with model: {}", request.model));
-        
+
+        let body = self.send_generate_request(&request, "generateContent", &[])?;
+
+        log_payload(self.payload_logging, "Got response", &body);
+
+        match serde_json::from_slice::<GenerateContentResponse>(&body) {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                log(&format!("Error parsing response: {}", e));
+                Err(GeminiError::SerdeError(e.to_string()))
+            }
+        }
+    }
+
+    /// Count the tokens a request would consume without generating anything
+    pub fn count_tokens(
+        &self,
+        request: GenerateContentRequest,
+    ) -> Result<CountTokensResponse, GeminiError> {
+        log(&format!("Counting tokens for model: {}", request.model));
+
+        let model = request.model.clone();
+        let body = if self.is_vertex() {
+            // Vertex takes the generate request fields directly
+            let request = GenerateContentRequest {
+                model: self.model_resource(&model),
+                ..request
+            };
+            self.post_model_method(&model, "countTokens", &[], &request)?
+        } else {
+            self.post_model_method(&model, "countTokens", &[], &CountTokensRequest::from(request))?
+        };
+
+        let response: CountTokensResponse = serde_json::from_slice(&body)?;
+        log(&format!("Request counts {} tokens", response.total_tokens));
+
+        Ok(response)
+    }
+
+    /// Embed texts with an embedding model.
+    ///
+    /// A single text goes through `embedContent`; larger inputs are split into
+    /// `batchEmbedContents` calls of at most `MAX_EMBED_BATCH_SIZE` texts each.
+    pub fn embed_contents(
+        &self,
+        model: &str,
+        texts: Vec<String>,
+        task_type: Option<TaskType>,
+        title: Option<String>,
+        output_dimensionality: Option<u32>,
+    ) -> Result<Vec<Vec<f32>>, GeminiError> {
+        log(&format!("Embedding {} texts with model: {}", texts.len(), model));
+
+        if self.is_vertex() {
+            return Err(GeminiError::UnsupportedFeature(
+                "Embeddings are only available on the Gemini Developer API".to_string(),
+            ));
+        }
+
+        let to_request = |text: String, model: Option<String>| EmbedContentRequest {
+            model,
+            content: Content {
+                role: Role::User,
+                parts: vec![Part {
+                    thought: None,
+                    data: PartData::Text(text),
+                }],
+            },
+            task_type,
+            title: title.clone(),
+            output_dimensionality,
+        };
+
+        if texts.len() == 1 {
+            let text = texts.into_iter().next().unwrap_or_default();
+            let body = self.post_model_method(model, "embedContent", &[], &to_request(text, None))?;
+            let response: EmbedContentResponse = serde_json::from_slice(&body)?;
+            return Ok(vec![response.embedding.values]);
+        }
+
+        let resource_name = format!("models/{}", model);
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(MAX_EMBED_BATCH_SIZE) {
+            let request = BatchEmbedContentsRequest {
+                requests: batch
+                    .iter()
+                    .map(|text| to_request(text.clone(), Some(resource_name.clone())))
+                    .collect(),
+            };
+
+            let body = self.post_model_method(model, "batchEmbedContents", &[], &request)?;
+            let response: BatchEmbedContentsResponse = serde_json::from_slice(&body)?;
+
+            if response.embeddings.len() != batch.len() {
+                return Err(GeminiError::InvalidResponse(format!(
+                    "Expected {} embeddings in batch, got {}",
+                    batch.len(),
+                    response.embeddings.len()
+                )));
+            }
+
+            embeddings.extend(response.embeddings.into_iter().map(|e| e.values));
+        }
+
+        Ok(embeddings)
+    }
+
+    /// POST a generate request to `{model}:{endpoint}` and return the raw body
+    fn send_generate_request(
+        &self,
+        request: &GenerateContentRequest,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<u8>, GeminiError> {
+        media::check_media(request)?;
+
+        // The body names the model by its full resource name
+        let mut request = request.clone();
+        let model = request.model.clone();
+        request.model = self.model_resource(&model);
+        request.cached_content = request
+            .cached_content
+            .map(|name| self.cached_content_resource(&name));
+
+        // Request-level safety settings override the defaults per category
+        if !self.safety_settings.is_empty() {
+            request.safety_settings = Some(merge_safety_settings(
+                &self.safety_settings,
+                request.safety_settings.as_deref().unwrap_or_default(),
+            ));
+        }
+
         // Log tool usage
         if let Some(tools) = &request.tools {
             for tool in tools {
@@ -229,20 +684,27 @@ impl GeminiClient {
             }
         }
 
-        // Create the full URL with the API key
-        let url = format!(
-            "{}/models/{}:{}?key={}",
-            self.base_url, request.model, endpoint, self.api_key
-        );
+        self.post_model_method(&model, endpoint, query, &request)
+    }
+
+    /// POST a JSON body to `{model}:{method}` and return the raw response body
+    fn post_model_method<T: Serialize>(
+        &self,
+        model: &str,
+        method: &str,
+        query: &[(&str, &str)],
+        body: &T,
+    ) -> Result<Vec<u8>, GeminiError> {
+        let url = self.url(&format!("{}:{}", self.model_resource(model), method), query);
 
         // Serialize the request body
-        let body = serde_json::to_vec(&request)?;
+        let body = serde_json::to_vec(body)?;
 
         // Create the HTTP request
         let http_request = HttpRequest {
             method: "POST".to_string(),
             uri: url,
-            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            headers: self.headers(),
             body: Some(body),
         };
 
@@ -259,19 +721,77 @@ impl GeminiClient {
             });
         }
 
-        // Parse the response
-        let body = response
+        response
             .body
-            .ok_or_else(|| GeminiError::InvalidResponse("No response body".to_string()))?;
+            .ok_or_else(|| GeminiError::InvalidResponse("No response body".to_string()))
+    }
+}
 
-        log(&format!("Got response: {}", String::from_utf8_lossy(&body)));
+/// Split a wait into sleeps the timing handler accepts
+pub fn sleep_chunks(ms: u32) -> impl Iterator<Item = u32> {
+    (0..ms.div_ceil(MAX_SLEEP_MS)).map(move |i| (ms - i * MAX_SLEEP_MS).min(MAX_SLEEP_MS))
+}
 
-        match serde_json::from_slice::<GenerateContentResponse>(&body) {
-            Ok(response) => Ok(response),
-            Err(e) => {
-                log(&format!("Error parsing response: {}", e));
-                Err(GeminiError::SerdeError(e.to_string()))
-            }
-        }
+/// Exponential backoff for `attempt` with equal jitter, capped by `max_delay_ms`.
+///
+/// Half of the delay is fixed and the other half is spread using `jitter_seed`, so
+/// concurrent callers that failed together do not retry in lockstep.
+pub(crate) fn backoff_delay(config: &RetryConfig, attempt: u32, jitter_seed: u64) -> u32 {
+    let delay = (config.base_delay_ms as f32 * config.backoff_multiplier.powi(attempt as i32))
+        .min(config.max_delay_ms as f32) as u32;
+
+    // xorshift scrambles the seed so neighbouring timestamps spread out
+    let mut x = jitter_seed ^ ((attempt as u64 + 1) << 32) ^ 0x9E37_79B9_7F4A_7C15;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+
+    let half = delay / 2;
+    half + (x % (delay - half + 1) as u64) as u32
+}
+
+/// The delay the server asked for, from a `Retry-After` header or a
+/// `google.rpc.RetryInfo` detail in the error body
+pub(crate) fn server_retry_delay_ms(response: &HttpResponse) -> Option<u32> {
+    let from_header = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+        .and_then(|(_, value)| value.trim().parse::<f64>().ok())
+        .map(|seconds| (seconds * 1000.0) as u32);
+
+    from_header.or_else(|| {
+        let body: serde_json::Value = serde_json::from_slice(response.body.as_deref()?).ok()?;
+        body["error"]["details"]
+            .as_array()?
+            .iter()
+            .filter(|detail| {
+                detail["@type"]
+                    .as_str()
+                    .is_some_and(|t| t.ends_with("google.rpc.RetryInfo"))
+            })
+            .find_map(|detail| parse_duration_ms(detail["retryDelay"].as_str()?))
+    })
+}
+
+/// Parse a protobuf JSON duration such as "37s" or "0.5s" into milliseconds
+pub(crate) fn parse_duration_ms(duration: &str) -> Option<u32> {
+    let seconds: f64 = duration.trim().strip_suffix('s')?.parse().ok()?;
+    if seconds < 0.0 {
+        return None;
     }
-}
\ No newline at end of file
+    Some((seconds * 1000.0).ceil() as u32)
+}
+
+/// Turn a non-success response into an API error
+pub(crate) fn check_status(response: HttpResponse) -> Result<HttpResponse, GeminiError> {
+    if (200..300).contains(&response.status) {
+        return Ok(response);
+    }
+    let message = String::from_utf8_lossy(&response.body.unwrap_or_default()).to_string();
+    log(&format!("API error: {} {}", response.status, message));
+    Err(GeminiError::ApiError {
+        status: response.status,
+        message,
+    })
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// How long a key rests after a quota error when the server gives no retry delay
+pub const DEFAULT_KEY_COOLDOWN_MS: u64 = 60_000;
+
+/// Shortest rest after a quota error, even when the server asks for no delay
+pub const MIN_KEY_COOLDOWN_MS: u64 = 1_000;
+
+/// An API key and when it may be used again
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PooledKey {
+    pub key: String,
+    /// Theater clock time in milliseconds before which the key is not used
+    #[serde(default)]
+    pub cooldown_until: u64,
+}
+
+impl std::fmt::Debug for PooledKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PooledKey")
+            .field("key", &"<redacted>")
+            .field("cooldown_until", &self.cooldown_until)
+            .finish()
+    }
+}
+
+/// Gemini Developer API keys, each usually from a project with its own quota.
+///
+/// Requests start on the keys in turn. A key that hits a quota error is put on
+/// cooldown and the request moves on to the next key that is not resting.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct KeyPool {
+    pub keys: Vec<PooledKey>,
+    /// Index of the key in use
+    #[serde(default)]
+    pub current: usize,
+}
+
+impl KeyPool {
+    pub fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|key| PooledKey {
+                    key,
+                    cooldown_until: 0,
+                })
+                .collect(),
+            current: 0,
+        }
+    }
+
+    /// Deserialize a key pool, or the single `api_key` string saved by versions
+    /// without key rotation
+    pub fn deserialize_or_single<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Saved {
+            Pool(KeyPool),
+            Single(String),
+        }
+
+        Ok(match Saved::deserialize(deserializer)? {
+            Saved::Pool(pool) => pool,
+            Saved::Single(key) => {
+                Self::new(Some(key).filter(|k| !k.is_empty()).into_iter().collect())
+            }
+        })
+    }
+
+    /// The key in use, if there are any keys
+    pub fn current_key(&self) -> Option<&str> {
+        self.keys.get(self.current).map(|k| k.key.as_str())
+    }
+
+    /// Move on to the next key for a new request.
+    ///
+    /// Keys on cooldown are skipped; if every key is resting, the one whose cooldown
+    /// ends first is used.
+    pub fn advance(&mut self, now: u64) {
+        if self.keys.is_empty() {
+            return;
+        }
+
+        match self.next_available(now) {
+            Some(index) => self.current = index,
+            None => {
+                self.current = (0..self.keys.len())
+                    .min_by_key(|&i| self.keys[i].cooldown_until)
+                    .unwrap_or(0);
+            }
+        }
+    }
+
+    /// Put the key in use on cooldown until `until`, or for at least
+    /// `MIN_KEY_COOLDOWN_MS`, and switch to the next key that is not resting,
+    /// returning false if there is none
+    pub fn cool_down_and_switch(&mut self, now: u64, until: u64) -> bool {
+        let until = until.max(now + MIN_KEY_COOLDOWN_MS);
+        if let Some(key) = self.keys.get_mut(self.current) {
+            key.cooldown_until = key.cooldown_until.max(until);
+        }
+
+        match self.next_available(now) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The first key after the current one, in round-robin order, that is not resting
+    fn next_available(&self, now: u64) -> Option<usize> {
+        let len = self.keys.len();
+        (1..=len)
+            .map(|offset| (self.current + offset) % len)
+            .find(|&i| self.keys[i].cooldown_until <= now)
+    }
+}
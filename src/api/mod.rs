@@ -1,2 +1,6 @@
+pub mod auth;
+pub mod caching;
+pub mod files;
 pub mod gemini;
+pub mod keys;
 pub use gemini::{GeminiClient, RetryConfig};
\ No newline at end of file
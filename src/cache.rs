@@ -0,0 +1,173 @@
+use crate::bindings::theater::simple::runtime::log;
+use crate::bindings::theater::simple::store;
+use crate::types::gemini::{GenerateContentRequest, GenerateContentResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Store label holding the LRU order of cached keys
+const INDEX_LABEL: &str = "google-proxy/cache/index";
+
+/// Prefix of the store label holding each cached response
+const ENTRY_LABEL_PREFIX: &str = "google-proxy/cache/entry/";
+
+/// LRU index of cached responses.
+///
+/// The responses themselves live in the Theater store under one label per key, and
+/// the index is written back to the store after every change so the cache survives
+/// actor restarts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ResponseCache {
+    /// Cache keys, least recently used first
+    pub keys: VecDeque<String>,
+}
+
+impl ResponseCache {
+    /// Read the index persisted in the store, starting empty if there is none
+    pub fn load(store_id: &str) -> Self {
+        let bytes = store::get_by_label(store_id, INDEX_LABEL)
+            .and_then(|content_ref| match content_ref {
+                Some(content_ref) => store::get(store_id, &content_ref).map(Some),
+                None => Ok(None),
+            });
+
+        match bytes {
+            Ok(Some(bytes)) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log(&format!("Ignoring unreadable cache index: {}", e));
+                Self::default()
+            }),
+            Ok(None) => Self::default(),
+            Err(e) => {
+                log(&format!("Error loading cache index: {}", e));
+                Self::default()
+            }
+        }
+    }
+
+    /// Mark `key` as most recently used, returning false if it is not cached
+    pub fn touch(&mut self, key: &str) -> bool {
+        match self.keys.iter().position(|k| k == key) {
+            Some(index) => {
+                if let Some(key) = self.keys.remove(index) {
+                    self.keys.push_back(key);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record `key` as most recently used and return the keys evicted to stay
+    /// within `capacity`
+    pub fn insert(&mut self, key: String, capacity: usize) -> Vec<String> {
+        if !self.touch(&key) {
+            self.keys.push_back(key);
+        }
+
+        let excess = self.keys.len().saturating_sub(capacity);
+        self.keys.drain(..excess).collect()
+    }
+
+    /// Look up a cached response, refreshing its position in the LRU order
+    pub fn get(&mut self, store_id: &str, key: &str) -> Option<GenerateContentResponse> {
+        if !self.touch(key) {
+            return None;
+        }
+
+        let label = entry_label(key);
+        let bytes = store::get_by_label(store_id, &label).and_then(|content_ref| {
+            content_ref
+                .map(|content_ref| store::get(store_id, &content_ref))
+                .transpose()
+        });
+
+        let response = match bytes {
+            Ok(Some(bytes)) => serde_json::from_slice(&bytes).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                log(&format!("Error reading cache entry {}: {}", key, e));
+                None
+            }
+        };
+
+        if response.is_none() {
+            // The entry is gone or unreadable; drop it from the index
+            self.keys.retain(|k| k != key);
+        }
+        self.persist(store_id);
+
+        response
+    }
+
+    /// Store a response and evict the least recently used entries beyond `capacity`
+    pub fn put(
+        &mut self,
+        store_id: &str,
+        key: String,
+        response: &GenerateContentResponse,
+        capacity: usize,
+    ) {
+        let bytes = match serde_json::to_vec(response) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log(&format!("Error serializing cache entry: {}", e));
+                return;
+            }
+        };
+
+        if let Err(e) = store::replace_content_at_label(store_id, &entry_label(&key), &bytes) {
+            log(&format!("Error writing cache entry {}: {}", key, e));
+            return;
+        }
+
+        for evicted in self.insert(key, capacity) {
+            if let Err(e) = store::remove_label(store_id, &entry_label(&evicted)) {
+                log(&format!("Error evicting cache entry {}: {}", evicted, e));
+            }
+        }
+        self.persist(store_id);
+    }
+
+    fn persist(&self, store_id: &str) {
+        let result = serde_json::to_vec(self)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| store::replace_content_at_label(store_id, INDEX_LABEL, &bytes));
+
+        if let Err(e) = result {
+            log(&format!("Error saving cache index: {}", e));
+        }
+    }
+}
+
+/// Whether a request's response may be served from the cache.
+///
+/// An explicit `opt_in` wins; otherwise only temperature-0 requests are treated as
+/// deterministic.
+pub fn is_cacheable(request: &GenerateContentRequest, opt_in: Option<bool>) -> bool {
+    opt_in.unwrap_or_else(|| {
+        request
+            .generation_config
+            .as_ref()
+            .and_then(|config| config.temperature)
+            == Some(0.0)
+    })
+}
+
+/// Canonical cache key for a request.
+///
+/// The request is hashed through `serde_json::Value`, whose object keys are sorted,
+/// so the key does not depend on field order.
+pub fn cache_key(request: &GenerateContentRequest) -> Result<String, serde_json::Error> {
+    let canonical = serde_json::to_value(request)?.to_string();
+    Ok(format!("{:016x}", stable_hash(canonical.as_bytes())))
+}
+
+/// 64-bit FNV-1a hash, stable across builds and restarts unlike `DefaultHasher`
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn entry_label(key: &str) -> String {
+    format!("{}{}", ENTRY_LABEL_PREFIX, key)
+}
@@ -1,16 +1,28 @@
-use crate::api::{GeminiClient, RetryConfig};
+use crate::api::GeminiClient;
 use crate::bindings::theater::simple::runtime::log;
-use crate::types::state::State;
-use genai_types::{ProxyRequest, ProxyResponse};
+use crate::bindings::theater::simple::timing;
+use crate::handlers::{build_client, generate_cached};
+use crate::logging::log_payload;
+use crate::types::cached_content::CachedContent;
+use crate::types::error::ErrorReport;
+use crate::types::files::{file_name, File, FileState};
+use crate::types::gemini::{
+    CompletionDetails, GeminiError, GeminiRequest, GeminiResponse, GenerateContentRequest,
+    ModelInfo,
+};
+use crate::types::state::{Config, State};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use genai_types::{CompletionResponse, ProxyRequest, ProxyResponse};
 
-pub fn handle_request(
-    data: Vec<u8>,
-    state_bytes: Vec<u8>,
-) -> Result<(Option<Vec<u8>>, (Option<Vec<u8>>,)), String> {
+/// Updated actor state paired with the response to a request
+pub type RequestResult = Result<(Option<Vec<u8>>, (Option<Vec<u8>>,)), String>;
+
+pub fn handle_request(data: Vec<u8>, state_bytes: Vec<u8>) -> RequestResult {
     log("Handling request in google-proxy actor");
 
     // Parse the state
-    let state: State = match serde_json::from_slice(&state_bytes) {
+    let mut state: State = match serde_json::from_slice(&state_bytes) {
         Ok(s) => s,
         Err(e) => {
             log(&format!("Error parsing state: {}", e));
@@ -18,21 +30,41 @@ pub fn handle_request(
         }
     };
 
-    // Debug log the incoming request
-    log(&format!(
-        "Received request data: {}",
-        String::from_utf8_lossy(&data)
-    ));
+    // Debug log the incoming request, without secrets or media
+    log_payload(state.config.payload_logging, "Received request data", &data);
 
-    // Parse the request
-    let request: ProxyRequest = match serde_json::from_slice(&data) {
-        Ok(req) => req,
+    // Create Gemini client with retry configuration
+    let client = match build_client(&mut state) {
+        Ok(client) => client,
+        Err(e) => {
+            log(&format!("Error creating client: {:?}", e));
+
+            // Both protocols share the shape of their error response
+            let error_response = ProxyResponse::Error {
+                error: ErrorReport::from_error("Failed to create client", &e).to_json(),
+            };
+
+            return match serde_json::to_vec(&error_response) {
+                Ok(bytes) => Ok((Some(state_bytes), (Some(bytes),))),
+                Err(_) => Err(format!("Failed to create client: {:?}", e)),
+            };
+        }
+    };
+
+    // Parse the request and route it to the protocol it belongs to
+    let response_bytes = match parse_request(&data) {
+        Ok(IncomingRequest::Proxy(request)) => {
+            serde_json::to_vec(&handle_proxy_request(&mut state, &client, request))
+        }
+        Ok(IncomingRequest::Gemini(request)) => {
+            serde_json::to_vec(&handle_gemini_request(&mut state, &client, request))
+        }
         Err(e) => {
             log(&format!("Error parsing request: {}", e));
 
             // Try to respond with a properly formatted error
             let error_response = ProxyResponse::Error {
-                error: format!("Invalid request format: {}", e),
+                error: ErrorReport::local(format!("Invalid request format: {}", e)).to_json(),
             };
 
             match serde_json::to_vec(&error_response) {
@@ -42,31 +74,76 @@ pub fn handle_request(
         }
     };
 
-    // Convert state retry config to API retry config
-    let retry_config = RetryConfig {
-        max_retries: state.config.retry_config.max_retries,
-        base_delay_ms: state.config.retry_config.base_delay_ms,
-        max_delay_ms: state.config.retry_config.max_delay_ms,
-        backoff_multiplier: state.config.retry_config.backoff_multiplier,
+    // Serialize the response
+    let response_bytes = match response_bytes {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log(&format!("Error serializing response: {}", e));
+            return Err(format!("Failed to serialize response: {}", e));
+        }
     };
 
-    // Create Gemini client with retry configuration
-    let client = GeminiClient::new_with_retry_config(state.api_key.clone(), retry_config);
+    // Keep the key rotation and any quota cooldowns for later requests
+    if let Some(api_keys) = client.key_pool() {
+        state.api_keys = api_keys;
+    }
+
+    // Serialize the state, which carries the cache index, keys and access token
+    let state_bytes = match serde_json::to_vec(&state) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log(&format!("Error serializing state: {}", e));
+            return Err(format!("Failed to serialize state: {}", e));
+        }
+    };
 
+    // Return the updated state and response
+    Ok((Some(state_bytes), (Some(response_bytes),)))
+}
+
+/// A request in one of the two protocols the actor accepts
+#[derive(Debug)]
+pub enum IncomingRequest {
+    Proxy(ProxyRequest),
+    Gemini(GeminiRequest),
+}
+
+/// Parse a request: the shared proxy protocol first, then Gemini-specific operations
+///
+/// `GeminiRequest` variants are named apart from `ProxyRequest`'s, so a Gemini request
+/// is never read as a shared one with its extra fields ignored.
+pub fn parse_request(data: &[u8]) -> Result<IncomingRequest, serde_json::Error> {
+    match serde_json::from_slice::<ProxyRequest>(data) {
+        Ok(request) => Ok(IncomingRequest::Proxy(request)),
+        Err(proxy_error) => serde_json::from_slice::<GeminiRequest>(data)
+            .map(IncomingRequest::Gemini)
+            .map_err(|_| proxy_error),
+    }
+}
+
+/// Process a request from the shared `genai_types` proxy protocol
+fn handle_proxy_request(
+    state: &mut State,
+    client: &GeminiClient,
+    request: ProxyRequest,
+) -> ProxyResponse {
     // Process based on operation type
-    let response = match request {
+    match request {
         ProxyRequest::GenerateCompletion { request } => match request.try_into() {
-            Ok(req) => match client.generate_content(req) {
-                Ok(content) => {
+            Ok(req) => match generate_cached(state, client, req, None, true) {
+                Ok((content, model)) => {
                     log("Content generated successfully");
                     // Convert the content to the expected format
-                    match content.try_into() {
-                        Ok(content) => ProxyResponse::Completion {
-                            completion: content,
+                    match CompletionResponse::try_from(content) {
+                        Ok(completion) => ProxyResponse::Completion {
+                            completion: CompletionResponse { model, ..completion },
                         },
                         Err(e) => {
                             log(&format!("Error converting content: {:?}", e));
-                            return Err(format!("Failed to convert content: {:?}", e));
+                            ProxyResponse::Error {
+                                error: ErrorReport::from_error("Failed to convert content", &e)
+                                    .to_json(),
+                            }
                         }
                     }
                 }
@@ -74,14 +151,14 @@ pub fn handle_request(
                 Err(e) => {
                     log(&format!("Error generating content: {:?}", e));
                     ProxyResponse::Error {
-                        error: format!("Failed to generate content: {:?}", e),
+                        error: ErrorReport::from_error("Failed to generate content", &e).to_json(),
                     }
                 }
             },
             Err(e) => {
                 log(&format!("Error converting request: {:?}", e));
                 ProxyResponse::Error {
-                    error: format!("Failed to convert request: {:?}", e),
+                    error: ErrorReport::from_error("Failed to convert request", &e).to_json(),
                 }
             }
         },
@@ -91,27 +168,330 @@ pub fn handle_request(
 
             match client.list_models() {
                 Ok(models) => ProxyResponse::ListModels {
-                    models: models.into_iter().map(|m| m.into()).collect(),
+                    models: with_prices(&state.config, models)
+                        .into_iter()
+                        .map(|m| m.into())
+                        .collect(),
                 },
                 Err(e) => {
                     log(&format!("Error listing models: {:?}", e));
                     ProxyResponse::Error {
-                        error: format!("Failed to list models: {:?}", e),
+                        error: ErrorReport::from_error("Failed to list models", &e).to_json(),
                     }
                 }
             }
         }
-    };
+    }
+}
 
-    // Serialize the response
-    let response_bytes = match serde_json::to_vec(&response) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            log(&format!("Error serializing response: {}", e));
-            return Err(format!("Failed to serialize response: {}", e));
+/// Process a Gemini-specific request
+fn handle_gemini_request(
+    state: &mut State,
+    client: &GeminiClient,
+    request: GeminiRequest,
+) -> GeminiResponse {
+    match request {
+        GeminiRequest::GenerateContent {
+            request,
+            model,
+            stream: _,
+            disable_fallback,
+        } => {
+            let mut request = *request;
+            request.model = model;
+
+            let content = generate_cached(state, client, request, None, !disable_fallback);
+
+            match content {
+                Ok((mut content, model)) => {
+                    // Keep Gemini's exact model version, naming the model that answered if it has none
+                    if content.model_version.is_empty() {
+                        content.model_version = model;
+                    }
+                    GeminiResponse::Content { content }
+                }
+                Err(e) => {
+                    log(&format!("Error generating content: {:?}", e));
+                    GeminiResponse::Error {
+                        error: ErrorReport::from_error("Failed to generate content", &e).to_json(),
+                    }
+                }
+            }
         }
-    };
 
-    // Return the updated state and response
-    Ok((Some(state_bytes), (Some(response_bytes),)))
-}
\ No newline at end of file
+        GeminiRequest::GenerateGeminiCompletion { request, options } => {
+            let opt_in = options.cache;
+            let allow_fallback = !options.disable_fallback;
+            let completion = GenerateContentRequest::from_completion(request, options)
+                .and_then(|req| generate_cached(state, client, req, opt_in, allow_fallback))
+                .and_then(|(content, model)| {
+                    let details = CompletionDetails::from(&content);
+                    let completion = CompletionResponse::try_from(content)?;
+                    Ok((CompletionResponse { model, ..completion }, details))
+                });
+
+            match completion {
+                Ok((completion, details)) => {
+                    log("Content generated successfully");
+                    GeminiResponse::Completion {
+                        completion,
+                        details,
+                    }
+                }
+                Err(e) => {
+                    log(&format!("Error generating completion: {:?}", e));
+                    GeminiResponse::Error {
+                        error: ErrorReport::from_error("Failed to generate completion", &e).to_json(),
+                    }
+                }
+            }
+        }
+
+        GeminiRequest::ListGeminiModels => {
+            log("Listing available models");
+
+            match client.list_models() {
+                Ok(models) => GeminiResponse::ListModels {
+                    models: with_prices(&state.config, models),
+                },
+                Err(e) => {
+                    log(&format!("Error listing models: {:?}", e));
+                    GeminiResponse::Error {
+                        error: ErrorReport::from_error("Failed to list models", &e).to_json(),
+                    }
+                }
+            }
+        }
+
+        GeminiRequest::CountTokens { request } => {
+            log("Counting tokens");
+
+            let result = GenerateContentRequest::try_from(request).and_then(|mut req| {
+                req.model = state.config.resolve_model(&req.model);
+                client.count_tokens(req)
+            });
+
+            match result {
+                Ok(count) => GeminiResponse::TokenCount {
+                    total_tokens: count.total_tokens,
+                    cached_content_token_count: count.cached_content_token_count,
+                },
+                Err(e) => {
+                    log(&format!("Error counting tokens: {:?}", e));
+                    GeminiResponse::Error {
+                        error: ErrorReport::from_error("Failed to count tokens", &e).to_json(),
+                    }
+                }
+            }
+        }
+
+        GeminiRequest::Embed {
+            model,
+            texts,
+            task_type,
+            title,
+            output_dimensionality,
+        } => {
+            // Aliases apply, but the default model is for generation, not embeddings
+            let model = state
+                .config
+                .model_aliases
+                .get(&model)
+                .cloned()
+                .unwrap_or(model);
+
+            if texts.is_empty() {
+                return GeminiResponse::Embeddings { embeddings: vec![] };
+            }
+
+            match client.embed_contents(&model, texts, task_type, title, output_dimensionality) {
+                Ok(embeddings) => GeminiResponse::Embeddings { embeddings },
+                Err(e) => {
+                    log(&format!("Error embedding content: {:?}", e));
+                    GeminiResponse::Error {
+                        error: ErrorReport::from_error("Failed to embed content", &e).to_json(),
+                    }
+                }
+            }
+        }
+
+        GeminiRequest::GetUsage => GeminiResponse::Usage {
+            models: state.usage.models.clone(),
+            total: state.usage.total(),
+        },
+
+        GeminiRequest::UploadFile {
+            data,
+            mime_type,
+            display_name,
+            wait_until_active,
+        } => {
+            let uploaded = STANDARD
+                .decode(data.trim())
+                .map_err(|e| GeminiError::InvalidRequest(format!("Invalid base64 file data: {}", e)))
+                .and_then(|bytes| client.upload_file(&bytes, &mime_type, display_name.as_deref()));
+
+            // Record the file before waiting, so it is tracked even if processing fails
+            let file = match uploaded {
+                Ok(file) => file,
+                Err(e) => return api_error("Failed to upload file", e),
+            };
+            state.files.insert(file.name.clone(), file.clone());
+
+            let file = match file.state {
+                FileState::Active => Ok(file),
+                _ if wait_until_active => client.wait_for_file(&file.name),
+                _ => Ok(file),
+            };
+            file_response(state, file, "Failed to wait for file")
+        }
+
+        GeminiRequest::GetFile { name } => {
+            file_response(state, client.get_file(&name), "Failed to get file")
+        }
+
+        GeminiRequest::WaitForFile { name } => {
+            file_response(state, client.wait_for_file(&name), "Failed to wait for file")
+        }
+
+        GeminiRequest::ListFiles {
+            page_size,
+            page_token,
+        } => match client.list_files(page_size, page_token.as_deref()) {
+            Ok(page) => {
+                for file in &page.files {
+                    state.files.insert(file.name.clone(), file.clone());
+                }
+                GeminiResponse::Files {
+                    files: page.files,
+                    next_page_token: page.next_page_token,
+                }
+            }
+            Err(e) => api_error("Failed to list files", e),
+        },
+
+        GeminiRequest::DeleteFile { name } => {
+            let name = file_name(&name);
+            match client.delete_file(&name) {
+                Ok(()) => {
+                    state.files.remove(&name);
+                    GeminiResponse::FileDeleted { name }
+                }
+                Err(e) => api_error("Failed to delete file", e),
+            }
+        }
+
+        GeminiRequest::CleanUpFiles => {
+            let now = timing::now();
+            let expired: Vec<String> = state
+                .files
+                .values()
+                .filter(|file| file.is_expired(now))
+                .map(|file| file.name.clone())
+                .collect();
+
+            // Files the API has already removed are gone too; any other failure keeps
+            // the file tracked so a later clean-up tries again
+            let mut names = Vec::new();
+            for name in expired {
+                match client.delete_file(&name) {
+                    Ok(()) | Err(GeminiError::ApiError { status: 404, .. }) => {
+                        state.files.remove(&name);
+                        names.push(name);
+                    }
+                    Err(e) => log(&format!("Error deleting expired file {}: {:?}", name, e)),
+                }
+            }
+            log(&format!("Deleted {} expired files", names.len()));
+            GeminiResponse::FilesCleanedUp { names }
+        }
+
+        GeminiRequest::CreateCachedContent { content } => {
+            let mut content = *content;
+            content.model = content
+                .model
+                .map(|model| state.config.resolve_model(&model));
+            cache_response(
+                client.create_cached_content(content),
+                "Failed to create cached content",
+            )
+        }
+
+        GeminiRequest::GetCachedContent { name } => cache_response(
+            client.get_cached_content(&name),
+            "Failed to get cached content",
+        ),
+
+        GeminiRequest::ListCachedContents {
+            page_size,
+            page_token,
+        } => match client.list_cached_contents(page_size, page_token.as_deref()) {
+            Ok(page) => GeminiResponse::CachedContents {
+                cached_contents: page.cached_contents,
+                next_page_token: page.next_page_token,
+            },
+            Err(e) => api_error("Failed to list cached contents", e),
+        },
+
+        GeminiRequest::UpdateCachedContentTtl { name, ttl_secs } => cache_response(
+            client.update_cached_content_ttl(&name, ttl_secs),
+            "Failed to update cached content",
+        ),
+
+        GeminiRequest::DeleteCachedContent { name } => {
+            match client.delete_cached_content(&name) {
+                Ok(()) => {
+                    let id = name.rsplit('/').next();
+                    state
+                        .context_caches
+                        .retain(|_, cached| cached.name.rsplit('/').next() != id);
+                    GeminiResponse::CachedContentDeleted { name }
+                }
+                Err(e) => api_error("Failed to delete cached content", e),
+            }
+        }
+    }
+}
+
+/// Record a fetched file in the state and return it
+fn file_response(
+    state: &mut State,
+    file: Result<File, GeminiError>,
+    context: &str,
+) -> GeminiResponse {
+    match file {
+        Ok(file) => {
+            state.files.insert(file.name.clone(), file.clone());
+            GeminiResponse::File { file }
+        }
+        Err(e) => api_error(context, e),
+    }
+}
+
+fn cache_response(
+    content: Result<CachedContent, GeminiError>,
+    context: &str,
+) -> GeminiResponse {
+    match content {
+        Ok(cached_content) => GeminiResponse::CachedContent { cached_content },
+        Err(e) => api_error(context, e),
+    }
+}
+
+fn api_error(context: &str, e: GeminiError) -> GeminiResponse {
+    log(&format!("{}: {:?}", context, e));
+    GeminiResponse::Error {
+        error: ErrorReport::from_error(context, &e).to_json(),
+    }
+}
+
+/// Fill in each model's prices from the configured price table
+fn with_prices(config: &Config, models: Vec<ModelInfo>) -> Vec<ModelInfo> {
+    models
+        .into_iter()
+        .map(|model| ModelInfo {
+            pricing: config.prices.get(&model.id).map(|&price| price.into()),
+            ..model
+        })
+        .collect()
+}
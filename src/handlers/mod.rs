@@ -1 +1,290 @@
 pub mod message;
+
+use crate::api::auth;
+use crate::api::{GeminiClient, RetryConfig};
+use crate::bindings::theater::simple::runtime::log;
+use crate::bindings::theater::simple::timing;
+use crate::cache;
+use crate::rate_limit::{self, RateLimitMode};
+use crate::types::cached_content::{prefix_key, CachedContent, CachedPrefix};
+use crate::types::files::parse_rfc3339_ms;
+use crate::types::gemini::{
+    GeminiError, GenerateContentRequest, GenerateContentResponse, UsageMetadata,
+};
+use crate::types::state::{Backend, Config, State};
+
+/// A context cache this close to expiring is replaced rather than reused
+const CONTEXT_CACHE_MARGIN_MS: u64 = 60_000;
+
+/// Create a Gemini client configured from the actor state.
+///
+/// On Vertex AI this mints an access token when the cached one is missing or about
+/// to expire, storing the new token in the state.
+pub fn build_client(state: &mut State) -> Result<GeminiClient, GeminiError> {
+    // Convert state retry config to API retry config
+    let retry_config = RetryConfig {
+        max_retries: state.config.retry_config.max_retries,
+        base_delay_ms: state.config.retry_config.base_delay_ms,
+        max_delay_ms: state.config.retry_config.max_delay_ms,
+        backoff_multiplier: state.config.retry_config.backoff_multiplier,
+    };
+
+    let client = match state.config.backend.clone() {
+        Backend::GoogleAi => {
+            // Spread requests over the keys, skipping any that are resting
+            state.api_keys.advance(timing::now());
+            GeminiClient::new_with_retry_config(state.api_keys.clone(), retry_config)
+        }
+        Backend::VertexAi(vertex) => {
+            let token = vertex_access_token(state, vertex.token_uri.as_deref())?;
+            GeminiClient::new_vertex(&vertex.project, &vertex.location, token, retry_config)
+        }
+    };
+
+    let client = match &state.config.base_url {
+        Some(base_url) => client.with_root_url(base_url),
+        None => client,
+    };
+    let client = match state.config.api_version {
+        Some(api_version) => client.with_api_version(api_version.as_str()),
+        None => client,
+    };
+
+    Ok(client
+        .with_headers(state.config.headers.clone())
+        .with_payload_logging(state.config.payload_logging)
+        .with_safety_settings(state.config.safety_settings.clone())
+        .with_timeout(state.config.timeout_ms))
+}
+
+/// Return the cached access token, minting a new one if it is missing or stale
+fn vertex_access_token(state: &mut State, token_uri: Option<&str>) -> Result<String, GeminiError> {
+    let now = timing::now();
+    if let Some(token) = state.access_token.as_ref().filter(|t| t.is_fresh(now)) {
+        return Ok(token.token.clone());
+    }
+
+    let key = auth::service_account_from_environment().map_err(GeminiError::InvalidRequest)?;
+
+    let token = auth::fetch_access_token(&key, token_uri, now)?;
+    log("Minted a new Vertex AI access token");
+
+    let value = token.token.clone();
+    state.access_token = Some(token);
+    Ok(value)
+}
+
+/// Models to try for a request to `model`: the model itself after default and alias
+/// resolution, then its configured fallbacks unless fallback is disabled
+pub fn model_chain(config: &Config, model: &str, allow_fallback: bool) -> Vec<String> {
+    let model = config.resolve_model(model);
+    let mut chain = vec![model.clone()];
+    if allow_fallback {
+        if let Some(fallbacks) = config.fallback_models.get(&model) {
+            chain.extend(
+                fallbacks
+                    .iter()
+                    .map(|m| config.resolve_model(m))
+                    .filter(|m| *m != model),
+            );
+        }
+    }
+    chain
+}
+
+/// Call `generate` for each model in `chain` until one is not overloaded or out of
+/// quota, returning its result and the model that produced it
+pub fn with_fallback<T>(
+    chain: &[String],
+    mut generate: impl FnMut(&str) -> Result<T, GeminiError>,
+) -> Result<(T, String), GeminiError> {
+    let mut last_error = None;
+
+    for model in chain {
+        match generate(model) {
+            Ok(result) => return Ok((result, model.clone())),
+            Err(e @ GeminiError::ApiError { status: 429 | 503, .. }) => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        GeminiError::InvalidRequest("No model to send the request to".to_string())
+    }))
+}
+
+/// Send `request` to `model` through `send` once the model's local rate limits allow
+/// it, then record the usage the result reports.
+///
+/// Over a limit, the request waits for the buckets to refill in queue mode as long
+/// as the deadline allows, and otherwise fails with `RateLimited`. With context
+/// caching on, a large prompt prefix is sent through a cache instead.
+pub fn send_paced<T>(
+    state: &mut State,
+    client: &GeminiClient,
+    request: &GenerateContentRequest,
+    model: &str,
+    send: impl Fn(GenerateContentRequest) -> Result<T, GeminiError>,
+    usage: impl FnOnce(&T) -> Option<UsageMetadata>,
+) -> Result<T, GeminiError> {
+    let full_request = GenerateContentRequest {
+        model: model.to_string(),
+        ..request.clone()
+    };
+    let request = use_context_cache(state, client, full_request.clone());
+    let limit = state.config.rate_limits.get(model).copied();
+    let estimated = rate_limit::estimate_tokens(&request);
+
+    if let Some(limit) = &limit {
+        while let Err(wait) = state.rate_limiter.acquire(model, limit, estimated, timing::now()) {
+            let wait_ms = wait.min(u32::MAX as u64) as u32;
+            let queued = state.config.rate_limit_mode == RateLimitMode::Queue && {
+                log(&format!("Local rate limit for {} reached, queueing", model));
+                client.wait_within_deadline(wait_ms)
+            };
+            if !queued {
+                return Err(GeminiError::RateLimited {
+                    model: model.to_string(),
+                    retry_after_ms: wait,
+                });
+            }
+        }
+    }
+
+    let cache_name = request
+        .cached_content
+        .clone()
+        .filter(|_| full_request.cached_content.is_none());
+    let result = match (send(request), cache_name) {
+        // The cache was deleted or expired early; forget it and send the whole prompt
+        (Err(GeminiError::ApiError { status: 403 | 404, .. }), Some(name)) => {
+            log(&format!("Cached content {} is gone, sending the full prompt", name));
+            state.context_caches.retain(|_, cached| cached.name != name);
+            send(full_request)
+        }
+        (result, _) => result,
+    }?;
+    if let Some(usage) = usage(&result) {
+        if let Some(limit) = &limit {
+            state.rate_limiter.settle(model, limit, estimated, usage.prompt_token_count);
+        }
+        state.usage.record(model, &usage, state.config.prices.get(model));
+    }
+    Ok(result)
+}
+
+/// Replace the prompt prefix of `request` with a context cache when automatic
+/// context caching is on and the prefix is large enough, creating the cache the
+/// first time the prefix is seen. The request is left whole if that fails.
+fn use_context_cache(
+    state: &mut State,
+    client: &GeminiClient,
+    request: GenerateContentRequest,
+) -> GenerateContentRequest {
+    let Some(settings) = state.config.context_cache else {
+        return request;
+    };
+    let Some(prefix) = settings.cacheable_prefix(&request) else {
+        return request;
+    };
+    let Some(key) = prefix_key(&prefix) else {
+        return request;
+    };
+
+    let now = timing::now();
+    state.context_caches.retain(|_, cached| cached.expires_at > now);
+    let name = match state.context_caches.get(&key) {
+        Some(cached) if cached.expires_at > now + CONTEXT_CACHE_MARGIN_MS => cached.name.clone(),
+        _ => {
+            let prefix = CachedContent {
+                ttl: Some(format!("{}s", settings.ttl_secs)),
+                ..prefix
+            };
+            let (name, expire_time) = match client.create_cached_content(prefix) {
+                Ok(CachedContent {
+                    name: Some(name),
+                    expire_time,
+                    ..
+                }) => (name, expire_time),
+                Ok(_) => {
+                    log("Created cached content has no name, sending the full prompt");
+                    return request;
+                }
+                Err(e) => {
+                    log(&format!("Failed to cache prompt prefix: {:?}", e));
+                    return request;
+                }
+            };
+            let expires_at = expire_time
+                .as_deref()
+                .and_then(parse_rfc3339_ms)
+                .unwrap_or(now + settings.ttl_secs * 1000);
+            state.context_caches.insert(
+                key,
+                CachedPrefix {
+                    name: name.clone(),
+                    expires_at,
+                },
+            );
+            name
+        }
+    };
+
+    settings.after_prefix(request, name)
+}
+
+/// Generate content, serving cacheable requests from the response cache.
+///
+/// `opt_in` overrides the default of caching only temperature-0 requests. Caching
+/// is skipped when `max_cache_size` is unset or zero, or when there is no store.
+/// Answers from a fallback model are never cached. Returns the response and the
+/// model in the fallback chain that answered.
+pub fn generate_cached(
+    state: &mut State,
+    client: &GeminiClient,
+    request: GenerateContentRequest,
+    opt_in: Option<bool>,
+    allow_fallback: bool,
+) -> Result<(GenerateContentResponse, String), GeminiError> {
+    let chain = model_chain(&state.config, &request.model, allow_fallback);
+
+    // Key the cache on the concrete model, so retargeting an alias takes effect
+    let request = GenerateContentRequest {
+        model: chain[0].clone(),
+        ..request
+    };
+
+    let capacity = state.config.max_cache_size.unwrap_or(0);
+    let cache_entry = match &state.store_id {
+        Some(store_id) if capacity > 0 && cache::is_cacheable(&request, opt_in) => {
+            let key = cache::cache_key(&request)
+                .map_err(|e| GeminiError::SerializationError(e.to_string()))?;
+            Some((store_id.clone(), key))
+        }
+        _ => None,
+    };
+
+    if let Some((store_id, key)) = &cache_entry {
+        if let Some(response) = state.cache.get(store_id, key) {
+            log(&format!("Serving cached response {}", key));
+            return Ok((response, request.model));
+        }
+    }
+
+    let (response, model) = with_fallback(&chain, |model| {
+        send_paced(
+            state,
+            client,
+            &request,
+            model,
+            |request| client.generate_content(request),
+            |response| response.usage_metadata.clone(),
+        )
+    })?;
+    if let Some((store_id, key)) = cache_entry {
+        if model == request.model {
+            state.cache.put(&store_id, key, &response, capacity);
+        }
+    }
+    Ok((response, model))
+}
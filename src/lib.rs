@@ -1,6 +1,11 @@
 mod api;
 mod bindings;
+mod cache;
 mod handlers;
+mod logging;
+mod media;
+mod rate_limit;
+mod usage;
 pub mod types;
 
 #[cfg(test)]
@@ -9,9 +14,11 @@ mod tests;
 use crate::bindings::exports::theater::simple::actor::Guest;
 use crate::bindings::exports::theater::simple::message_server_client::Guest as MessageServerClient;
 use crate::bindings::theater::simple::runtime::log;
-use crate::types::state::{InitConfig, State};
+use crate::api::auth::service_account_from_environment;
+use crate::types::state::{Backend, InitConfig, State};
 
 use bindings::theater::simple::environment;
+use bindings::theater::simple::store;
 use bindings::theater::simple::types::ChannelAccept;
 use serde::{Deserialize, Serialize};
 
@@ -44,18 +51,57 @@ impl Guest for Component {
 
         log("Init data parsed successfully");
 
-        let google_api_key = match environment::get_var("GEMINI_API_KEY") {
-            Some(key) => {
-                log("Google API key found in environment");
-                key
+        let backend = init_data
+            .config
+            .as_ref()
+            .and_then(|config| config.backend.clone())
+            .unwrap_or_default();
+
+        // Vertex AI authenticates with a service account instead of API keys
+        // The key is only checked here; it is read again whenever a token is minted
+        let google_api_keys = match backend {
+            Backend::GoogleAi => {
+                let keys = api_keys_from_environment();
+                if keys.is_empty() {
+                    return Err("Google API key not found in environment".to_string());
+                }
+                log(&format!("{} Google API key(s) found in environment", keys.len()));
+                keys
             }
-            None => {
-                return Err("Google API key not found in environment".to_string());
+            Backend::VertexAi(_) => {
+                let key = service_account_from_environment()?;
+                log(&format!("Using service account {}", key.client_email));
+                vec![]
             }
         };
 
+        // A limit of 0 would never hold a request back, so it is refused here
+        if let Some(rate_limits) = init_data
+            .config
+            .as_ref()
+            .and_then(|config| config.rate_limits.as_ref())
+        {
+            for (model, limit) in rate_limits {
+                limit.validate(model)?;
+            }
+        }
+
         // Initialize state
-        let state = State::new(id, google_api_key, init_data.store_id, init_data.config);
+        let mut state = State::new(id, google_api_keys, init_data.store_id, init_data.config);
+
+        // The response cache lives in the store; reuse the given one so it survives restarts
+        if state.config.max_cache_size.unwrap_or(0) > 0 {
+            match &state.store_id {
+                Some(store_id) => state.cache = cache::ResponseCache::load(store_id),
+                None => match store::new() {
+                    Ok(store_id) => {
+                        log(&format!("Created store {} for the response cache", store_id));
+                        state.store_id = Some(store_id);
+                    }
+                    Err(e) => log(&format!("Response cache disabled, no store: {}", e)),
+                },
+            }
+        }
 
         log("State initialized");
 
@@ -70,6 +116,23 @@ impl Guest for Component {
     }
 }
 
+/// Read the comma-separated `GEMINI_API_KEYS`, falling back to the single `GEMINI_API_KEY`
+fn api_keys_from_environment() -> Vec<String> {
+    if let Some(keys) = environment::get_var("GEMINI_API_KEYS") {
+        let keys: Vec<String> = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(String::from)
+            .collect();
+        if !keys.is_empty() {
+            return keys;
+        }
+    }
+
+    environment::get_var("GEMINI_API_KEY").into_iter().collect()
+}
+
 impl MessageServerClient for Component {
     fn handle_send(
         state: Option<Vec<u8>>,
@@ -99,11 +162,15 @@ impl MessageServerClient for Component {
     ) -> Result<(Option<Vec<u8>>, (ChannelAccept,)), String> {
         log("Channel open request received");
 
+        // The host http-client only returns whole responses, so there is nothing to stream
         Ok((
             state,
             (ChannelAccept {
-                accepted: true,
-                message: None,
+                accepted: false,
+                message: Some(
+                    b"Streaming is not supported: completions are only available as whole responses"
+                        .to_vec(),
+                ),
             },),
         ))
     }
@@ -0,0 +1,126 @@
+use crate::bindings::theater::simple::runtime::log;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Object keys whose values are credentials and are never logged
+const SECRET_KEYS: &[&str] = &[
+    "key",
+    "api_key",
+    "apikey",
+    "x-goog-api-key",
+    "authorization",
+    "access_token",
+    "private_key",
+    "assertion",
+];
+
+/// Keys of objects carrying base64 media, or of arrays of such objects, whose
+/// `data` is never logged
+const MEDIA_KEYS: &[&str] = &["inlineData", "inline_data", "attachments", "UploadFile"];
+
+/// Keys naming a MIME type; an object holding one carries media in its `data`
+const MIME_TYPE_KEYS: &[&str] = &["mimeType", "mime_type"];
+
+/// Keys holding prompt or answer text
+const TEXT_KEYS: &[&str] = &["text", "texts", "system"];
+
+/// How much of request and response payloads is written to the log
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadLogging {
+    /// Only payload sizes are logged
+    Off,
+    /// Payloads are logged with secrets and media removed and text replaced by its length
+    #[default]
+    Redacted,
+    /// Payloads are logged with secrets and media removed but text kept
+    Full,
+}
+
+/// Log a JSON payload at the configured level
+pub fn log_payload(level: PayloadLogging, label: &str, payload: &[u8]) {
+    log(&format_payload(level, label, payload));
+}
+
+/// The log line for a payload at the given level
+pub fn format_payload(level: PayloadLogging, label: &str, payload: &[u8]) -> String {
+    match level {
+        PayloadLogging::Off => format!("{}: {} bytes", label, payload.len()),
+        _ => {
+            let text = match serde_json::from_slice::<Value>(payload) {
+                Ok(mut value) => {
+                    redact(&mut value, level == PayloadLogging::Redacted);
+                    value.to_string()
+                }
+                // Bodies that are not JSON could hold anything
+                Err(_) => format!("<{} bytes of non-JSON data>", payload.len()),
+            };
+            format!("{}: {}", label, text)
+        }
+    }
+}
+
+/// Remove secrets and media bytes from a JSON value, and text too if `redact_text`
+pub fn redact(value: &mut Value, redact_text: bool) {
+    match value {
+        Value::Object(object) => {
+            // Such as tool result images, which no media key leads to
+            if MIME_TYPE_KEYS.iter().any(|key| object.contains_key(*key)) {
+                if let Some(data) = object.get_mut("data") {
+                    redact_data(data);
+                }
+            }
+            for (key, field) in object.iter_mut() {
+                if SECRET_KEYS.contains(&key.to_ascii_lowercase().as_str()) {
+                    *field = Value::String("<redacted>".to_string());
+                } else if MEDIA_KEYS.contains(&key.as_str()) {
+                    redact_media(field);
+                } else if redact_text && TEXT_KEYS.contains(&key.as_str()) {
+                    redact_strings(field);
+                } else {
+                    redact(field, redact_text);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact(item, redact_text);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace the `data` of a media object, or of every object in an array, by its length
+fn redact_media(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(redact_media),
+        _ => {
+            if let Some(data) = value.get_mut("data") {
+                redact_data(data);
+            }
+        }
+    }
+}
+
+/// Replace media bytes by their length
+fn redact_data(data: &mut Value) {
+    *data = Value::String(format!("<{} chars of media>", summarize_len(data)));
+}
+
+/// Replace every string in `value` by its length
+fn redact_strings(value: &mut Value) {
+    match value {
+        Value::String(text) => *value = Value::String(format!("<{} chars>", text.chars().count())),
+        Value::Array(items) => items.iter_mut().for_each(redact_strings),
+        _ => {}
+    }
+}
+
+fn summarize_len(value: &Value) -> usize {
+    match value {
+        Value::String(text) => text.len(),
+        Value::Array(items) => items.len(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,131 @@
+use crate::types::gemini::{GeminiError, GenerateContentRequest, PartData};
+
+/// Largest request body Gemini accepts with inline media; bigger files must be
+/// uploaded and referenced by URI
+pub const MAX_INLINE_REQUEST_BYTES: usize = 20 * 1024 * 1024;
+
+const IMAGE_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/webp",
+    "image/heic",
+    "image/heif",
+];
+
+const AUDIO_TYPES: &[&str] = &[
+    "audio/wav",
+    "audio/mp3",
+    "audio/mpeg",
+    "audio/aiff",
+    "audio/aac",
+    "audio/ogg",
+    "audio/flac",
+];
+
+const VIDEO_TYPES: &[&str] = &[
+    "video/mp4",
+    "video/mpeg",
+    "video/mov",
+    "video/quicktime",
+    "video/avi",
+    "video/x-flv",
+    "video/mpg",
+    "video/webm",
+    "video/wmv",
+    "video/3gpp",
+];
+
+const DOCUMENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "text/plain",
+    "text/html",
+    "text/css",
+    "text/csv",
+    "text/xml",
+    "text/md",
+    "text/markdown",
+    "text/rtf",
+    "text/javascript",
+    "text/x-python",
+];
+
+/// The kinds of media input Gemini understands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaKind {
+    Image,
+    Audio,
+    Video,
+    Document,
+}
+
+/// The kind of media a MIME type holds, if Gemini accepts it at all
+pub fn media_kind(mime_type: &str) -> Option<MediaKind> {
+    let mime_type = mime_type.trim().to_ascii_lowercase();
+    let mime_type = mime_type.as_str();
+    [
+        (IMAGE_TYPES, MediaKind::Image),
+        (AUDIO_TYPES, MediaKind::Audio),
+        (VIDEO_TYPES, MediaKind::Video),
+        (DOCUMENT_TYPES, MediaKind::Document),
+    ]
+    .into_iter()
+    .find(|(types, _)| types.contains(&mime_type))
+    .map(|(_, kind)| kind)
+}
+
+/// Whether `model` takes media of `kind` as input.
+///
+/// Gemini models take every kind; Gemma takes images only, and embedding and
+/// attributed-QA models take text only.
+pub fn model_accepts(model: &str, kind: MediaKind) -> bool {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    if model.starts_with("gemma-") {
+        kind == MediaKind::Image
+    } else {
+        !(model.contains("embedding") || model.starts_with("aqa"))
+    }
+}
+
+/// Check the media in a request against what its model accepts, and the request's
+/// size against the inline limit
+pub fn check_media(request: &GenerateContentRequest) -> Result<(), GeminiError> {
+    let mut has_inline_data = false;
+
+    let parts = request
+        .contents
+        .iter()
+        .chain(&request.system_instruction)
+        .flat_map(|content| &content.parts);
+    for part in parts {
+        let mime_type = match &part.data {
+            PartData::InlineData(blob) => {
+                has_inline_data = true;
+                &blob.mime_type
+            }
+            PartData::FileData(file) => &file.mime_type,
+            _ => continue,
+        };
+
+        let kind = media_kind(mime_type).ok_or_else(|| {
+            GeminiError::InvalidRequest(format!("Unsupported media MIME type {}", mime_type))
+        })?;
+        if !model_accepts(&request.model, kind) {
+            return Err(GeminiError::InvalidRequest(format!(
+                "Model {} does not accept {:?} input ({})",
+                request.model, kind, mime_type
+            )));
+        }
+    }
+
+    if has_inline_data {
+        let size = serde_json::to_vec(request)?.len();
+        if size > MAX_INLINE_REQUEST_BYTES {
+            return Err(GeminiError::InvalidRequest(format!(
+                "Request with inline media is {} bytes, over the {} byte limit; upload large files and send them as file data instead",
+                size, MAX_INLINE_REQUEST_BYTES
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::types::gemini::GenerateContentRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const MINUTE_MS: u64 = 60_000;
+const DAY_MS: u64 = 24 * 60 * MINUTE_MS;
+
+/// Rough number of characters per token, used to estimate prompt size up front
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Local limits for one model. Unset limits are not enforced.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimit {
+    /// Requests per minute
+    #[serde(default)]
+    pub rpm: Option<u32>,
+
+    /// Prompt tokens per minute
+    #[serde(default)]
+    pub tpm: Option<u32>,
+
+    /// Requests per day
+    #[serde(default)]
+    pub rpd: Option<u32>,
+}
+
+impl RateLimit {
+    /// Reject limits of 0, which a token bucket cannot enforce; leave a limit unset
+    /// to disable it
+    pub fn validate(&self, model: &str) -> Result<(), String> {
+        for (name, value) in [("rpm", self.rpm), ("tpm", self.tpm), ("rpd", self.rpd)] {
+            if value == Some(0) {
+                return Err(format!(
+                    "Rate limit {} for {} must be at least 1; leave it unset to disable it",
+                    name, model
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What happens to a request when a local limit is exhausted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitMode {
+    /// Wait for the bucket to refill, as long as the request deadline allows
+    #[default]
+    Queue,
+    /// Fail straight away with the time to wait
+    Reject,
+}
+
+/// A token bucket that refills continuously up to its capacity over a window
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    /// Units left; may go negative when a request used more tokens than estimated
+    pub available: f64,
+    /// Theater clock time in milliseconds of the last refill
+    pub updated_at: u64,
+}
+
+impl Bucket {
+    fn full(capacity: u32, now: u64) -> Self {
+        Self {
+            available: capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, capacity: u32, window_ms: u64, now: u64) {
+        let elapsed = now.saturating_sub(self.updated_at) as f64;
+        let refilled = self.available + elapsed * capacity as f64 / window_ms as f64;
+        self.available = refilled.min(capacity as f64);
+        self.updated_at = self.updated_at.max(now);
+    }
+
+    /// Milliseconds until `cost` units are available. A cost above the capacity only
+    /// waits for a full bucket, so oversized requests are not stuck forever.
+    fn wait_ms(&self, cost: u32, capacity: u32, window_ms: u64) -> u64 {
+        let cost = cost.min(capacity) as f64;
+        if self.available >= cost || capacity == 0 {
+            return 0;
+        }
+        ((cost - self.available) * window_ms as f64 / capacity as f64).ceil() as u64
+    }
+}
+
+/// Buckets for the limits configured on one model
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelBuckets {
+    #[serde(default)]
+    pub requests: Option<Bucket>,
+    #[serde(default)]
+    pub tokens: Option<Bucket>,
+    #[serde(default)]
+    pub daily_requests: Option<Bucket>,
+}
+
+/// Per-model token buckets enforcing the configured rate limits.
+///
+/// A request takes one unit from the request buckets and its estimated prompt
+/// tokens from the token bucket. Once the response reports the actual prompt
+/// tokens, the difference is settled.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RateLimiter {
+    pub buckets: BTreeMap<String, ModelBuckets>,
+}
+
+impl RateLimiter {
+    /// Take capacity for one request of `tokens` estimated tokens, or return how many
+    /// milliseconds to wait before it would fit. Nothing is taken if it does not fit.
+    pub fn acquire(
+        &mut self,
+        model: &str,
+        limit: &RateLimit,
+        tokens: u32,
+        now: u64,
+    ) -> Result<(), u64> {
+        let buckets = self.buckets.entry(model.to_string()).or_default();
+        let limits = [
+            (&mut buckets.requests, limit.rpm, 1, MINUTE_MS),
+            (&mut buckets.tokens, limit.tpm, tokens, MINUTE_MS),
+            (&mut buckets.daily_requests, limit.rpd, 1, DAY_MS),
+        ];
+
+        let mut wait = 0;
+        let mut takes = Vec::new();
+        for (bucket, capacity, cost, window_ms) in limits {
+            let Some(capacity) = capacity else {
+                continue;
+            };
+            let bucket = bucket.get_or_insert_with(|| Bucket::full(capacity, now));
+            bucket.refill(capacity, window_ms, now);
+            wait = wait.max(bucket.wait_ms(cost, capacity, window_ms));
+            takes.push((bucket, cost));
+        }
+
+        if wait > 0 {
+            return Err(wait);
+        }
+        for (bucket, cost) in takes {
+            bucket.available -= cost as f64;
+        }
+        Ok(())
+    }
+
+    /// Correct the token bucket once the actual prompt tokens of a request are known
+    pub fn settle(&mut self, model: &str, limit: &RateLimit, estimated: u32, actual: u32) {
+        let (Some(capacity), Some(bucket)) = (
+            limit.tpm,
+            self.buckets
+                .get_mut(model)
+                .and_then(|buckets| buckets.tokens.as_mut()),
+        ) else {
+            return;
+        };
+        let settled = bucket.available + estimated as f64 - actual as f64;
+        bucket.available = settled.min(capacity as f64);
+    }
+}
+
+/// Estimate the prompt tokens of a request from the size of its content.
+///
+/// Base64 bytes of inline media are left out, since they are not sent to the model
+/// as text.
+pub fn estimate_tokens(request: &GenerateContentRequest) -> u32 {
+    let mut prompt = serde_json::to_value((
+        &request.contents,
+        &request.system_instruction,
+        &request.tools,
+    ))
+    .unwrap_or_default();
+    strip_inline_data(&mut prompt);
+    estimate_json_tokens(&prompt)
+}
+
+/// Remove the `data` of every `inlineData` part
+fn strip_inline_data(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Object(inline)) = object.get_mut("inlineData") {
+                inline.remove("data");
+            }
+            object.values_mut().for_each(strip_inline_data);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_inline_data),
+        _ => {}
+    }
+}
+
+/// Estimate the tokens of any prompt material from the size of its JSON
+pub fn estimate_json_tokens(value: &impl Serialize) -> u32 {
+    let size = serde_json::to_string(value)
+        .map(|json| json.len())
+        .unwrap_or(0);
+    (size / CHARS_PER_TOKEN).max(1) as u32
+}
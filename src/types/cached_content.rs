@@ -0,0 +1,151 @@
+use crate::cache::stable_hash;
+use crate::rate_limit::estimate_json_tokens;
+use crate::types::gemini::{Content, GenerateContentRequest, Tool, ToolConfig};
+use serde::{Deserialize, Serialize};
+
+/// Token count of a cached content
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsage {
+    #[serde(default)]
+    pub total_token_count: u32,
+}
+
+/// A prompt prefix cached server-side through the `cachedContents` API.
+///
+/// The same shape is sent to create a cache and returned for existing ones; the
+/// contents, system instruction and tools are never returned.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+    /// Resource name, e.g. "cachedContents/abc-123"; assigned by the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The model the cache is for; a cache only serves requests to that model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contents: Vec<Content>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<Content>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+
+    /// Time to live as a protobuf duration, such as "3600s"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+
+    /// RFC 3339 time at which the cache is deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<CachedContentUsage>,
+}
+
+/// Response from `cachedContents.list`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCachedContentsResponse {
+    #[serde(default)]
+    pub cached_contents: Vec<CachedContent>,
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+}
+
+/// Settings for caching large, stable prompt prefixes automatically
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ContextCacheConfig {
+    /// Smallest estimated prefix, in tokens, worth caching
+    #[serde(default = "default_min_tokens")]
+    pub min_tokens: u32,
+
+    /// How many leading messages belong to the prefix, besides the system
+    /// instruction and tools
+    #[serde(default = "default_prefix_messages")]
+    pub prefix_messages: usize,
+
+    /// Lifetime of each cache, in seconds
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+fn default_min_tokens() -> u32 {
+    32_768
+}
+
+fn default_prefix_messages() -> usize {
+    1
+}
+
+fn default_ttl_secs() -> u64 {
+    3600
+}
+
+/// Handle of a cache the proxy created for a prompt prefix
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedPrefix {
+    /// Resource name of the cached content
+    pub name: String,
+    /// Expiry in Unix milliseconds
+    pub expires_at: u64,
+}
+
+impl ContextCacheConfig {
+    /// The part of `request` worth caching: its system instruction, tools and first
+    /// `prefix_messages` messages. There is none if they are estimated below
+    /// `min_tokens`, or if no message would be left to send after them.
+    pub fn cacheable_prefix(&self, request: &GenerateContentRequest) -> Option<CachedContent> {
+        if request.cached_content.is_some() || request.contents.len() <= self.prefix_messages {
+            return None;
+        }
+
+        let prefix = CachedContent {
+            model: Some(request.model.clone()),
+            contents: request.contents[..self.prefix_messages].to_vec(),
+            system_instruction: request.system_instruction.clone(),
+            tools: request.tools.clone(),
+            tool_config: request.tool_config.clone(),
+            ..Default::default()
+        };
+        (estimate_json_tokens(&prefix) >= self.min_tokens).then_some(prefix)
+    }
+
+    /// `request` with its prefix replaced by the cache `name`
+    pub fn after_prefix(
+        &self,
+        request: GenerateContentRequest,
+        name: String,
+    ) -> GenerateContentRequest {
+        GenerateContentRequest {
+            contents: request.contents[self.prefix_messages..].to_vec(),
+            system_instruction: None,
+            tools: None,
+            tool_config: None,
+            cached_content: Some(name),
+            ..request
+        }
+    }
+}
+
+/// Key identifying a prefix, the same for every request that starts with it
+pub fn prefix_key(prefix: &CachedContent) -> Option<String> {
+    let bytes = serde_json::to_vec(prefix).ok()?;
+    Some(format!("{:016x}", stable_hash(&bytes)))
+}
@@ -0,0 +1,256 @@
+use crate::api::gemini::parse_duration_ms;
+use crate::types::gemini::{GeminiError, SafetyRating};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Stable classification of an error, for callers to branch on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Missing, invalid or expired credentials, or no permission for the resource
+    Auth,
+    /// A quota or rate limit was hit, at Google or locally
+    Quota,
+    /// Google rejected the request, for example an unknown model or a bad field
+    InvalidArgument,
+    /// The prompt or the answer was blocked by safety filters
+    Safety,
+    /// The service was unavailable, overloaded or could not be reached
+    Overloaded,
+    /// The request deadline passed
+    Timeout,
+    /// The proxy rejected the request before sending it
+    LocalValidation,
+    /// Anything else, such as a response the proxy could not understand
+    Internal,
+}
+
+/// One entry of a `google.rpc.QuotaFailure`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QuotaViolation {
+    #[serde(default, alias = "quotaMetric", skip_serializing_if = "Option::is_none")]
+    pub quota_metric: Option<String>,
+    #[serde(default, alias = "quotaId", skip_serializing_if = "Option::is_none")]
+    pub quota_id: Option<String>,
+    #[serde(default, alias = "quotaValue", skip_serializing_if = "Option::is_none")]
+    pub quota_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// One entry of a `google.rpc.BadRequest`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FieldViolation {
+    #[serde(default)]
+    pub field: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A machine-readable error, sent JSON-encoded as the `error` of error responses
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    pub category: ErrorCategory,
+
+    /// Human-readable description
+    pub message: String,
+
+    /// HTTP status of the API response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+
+    /// `google.rpc.Code` name, such as `RESOURCE_EXHAUSTED`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+
+    /// `ErrorInfo` reason, or the block or finish reason of a safety error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// How long to wait before trying again, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quota_violations: Vec<QuotaViolation>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub field_violations: Vec<FieldViolation>,
+
+    /// Safety ratings of a blocked prompt or candidate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+/// The `google.rpc.Status` inside Google's error envelope
+#[derive(Deserialize, Debug, Default)]
+struct RpcStatus {
+    #[serde(default)]
+    code: Option<u16>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    details: Vec<Value>,
+}
+
+impl ErrorReport {
+    fn new(category: ErrorCategory, message: String) -> Self {
+        Self {
+            category,
+            message,
+            code: None,
+            status: None,
+            reason: None,
+            retry_after_ms: None,
+            quota_violations: vec![],
+            field_violations: vec![],
+            safety_ratings: vec![],
+        }
+    }
+
+    /// An error detected by the proxy itself, before anything was sent
+    pub fn local(message: impl Into<String>) -> Self {
+        Self::new(ErrorCategory::LocalValidation, message.into())
+    }
+
+    /// Report `error`, with `context` saying what failed
+    pub fn from_error(context: &str, error: &GeminiError) -> Self {
+        let described =
+            |category, detail: &str| Self::new(category, format!("{}: {}", context, detail));
+
+        match error {
+            GeminiError::ApiError { status, message } => {
+                Self::from_api_error(context, *status, message)
+            }
+            GeminiError::HttpError(e) => described(ErrorCategory::Overloaded, e),
+            GeminiError::InvalidRequest(e)
+            | GeminiError::UnsupportedFeature(e)
+            | GeminiError::SerializationError(e) => described(ErrorCategory::LocalValidation, e),
+            GeminiError::InvalidResponse(e) | GeminiError::SerdeError(e) => {
+                described(ErrorCategory::Internal, e)
+            }
+            GeminiError::Timeout { timeout_ms } => Self::new(
+                ErrorCategory::Timeout,
+                format!("{}: deadline of {}ms exceeded", context, timeout_ms),
+            ),
+            GeminiError::RateLimited {
+                model,
+                retry_after_ms,
+            } => Self {
+                reason: Some("LOCAL_RATE_LIMIT".to_string()),
+                retry_after_ms: Some(*retry_after_ms),
+                ..Self::new(
+                    ErrorCategory::Quota,
+                    format!(
+                        "{}: rate limited locally for {}, retry after {} ms",
+                        context, model, retry_after_ms
+                    ),
+                )
+            },
+            GeminiError::PromptBlocked {
+                block_reason,
+                safety_ratings,
+            } => Self {
+                reason: Some(block_reason.clone()),
+                safety_ratings: safety_ratings.clone(),
+                ..Self::new(
+                    ErrorCategory::Safety,
+                    format!("{}: prompt blocked ({})", context, block_reason),
+                )
+            },
+            GeminiError::ResponseBlocked {
+                finish_reason,
+                safety_ratings,
+            } => {
+                let reason = serde_json::to_value(finish_reason)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_else(|| format!("{:?}", finish_reason));
+                Self {
+                    message: format!("{}: response blocked ({})", context, reason),
+                    reason: Some(reason),
+                    safety_ratings: safety_ratings.clone(),
+                    ..Self::new(ErrorCategory::Safety, String::new())
+                }
+            }
+        }
+    }
+
+    /// Parse Google's `{"error": google.rpc.Status}` envelope out of an error body,
+    /// falling back to the raw body when it is not one
+    fn from_api_error(context: &str, code: u16, body: &str) -> Self {
+        let rpc = parse_rpc_status(body).unwrap_or_default();
+        let mut report = Self::new(
+            ErrorCategory::Internal,
+            format!("{}: {}", context, rpc.message.as_deref().unwrap_or(body)),
+        );
+        report.code = Some(code);
+        report.status = rpc.status;
+
+        for detail in &rpc.details {
+            let kind = detail["@type"].as_str().unwrap_or_default();
+            if kind.ends_with("google.rpc.RetryInfo") {
+                report.retry_after_ms = detail["retryDelay"]
+                    .as_str()
+                    .and_then(parse_duration_ms)
+                    .map(u64::from);
+            } else if kind.ends_with("google.rpc.ErrorInfo") {
+                report.reason = detail["reason"].as_str().map(str::to_string);
+            } else if kind.ends_with("google.rpc.QuotaFailure") {
+                report.quota_violations = parse_list(&detail["violations"]);
+            } else if kind.ends_with("google.rpc.BadRequest") {
+                report.field_violations = parse_list(&detail["fieldViolations"]);
+            }
+        }
+
+        report.category = api_error_category(
+            rpc.code.unwrap_or(code),
+            report.status.as_deref(),
+            report.reason.as_deref(),
+        );
+        report
+    }
+
+    /// The JSON string sent as the `error` of an error response
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.message.clone())
+    }
+}
+
+/// Categorize an API error from its rpc status name, falling back to the HTTP code
+fn api_error_category(code: u16, status: Option<&str>, reason: Option<&str>) -> ErrorCategory {
+    // An invalid key is reported as INVALID_ARGUMENT, with the reason telling it apart
+    if reason.is_some_and(|reason| reason.starts_with("API_KEY_")) {
+        return ErrorCategory::Auth;
+    }
+
+    match (status, code) {
+        (Some("UNAUTHENTICATED" | "PERMISSION_DENIED"), _) | (None, 401 | 403) => {
+            ErrorCategory::Auth
+        }
+        (Some("RESOURCE_EXHAUSTED"), _) | (None, 429) => ErrorCategory::Quota,
+        (Some("DEADLINE_EXCEEDED"), _) | (None, 504) => ErrorCategory::Timeout,
+        (Some("UNAVAILABLE" | "INTERNAL"), _) | (None, 500..=599) => ErrorCategory::Overloaded,
+        _ => ErrorCategory::InvalidArgument,
+    }
+}
+
+/// The rpc status in an error body. Vertex AI sometimes wraps the envelope in an
+/// array, and some messages prefix the body with context.
+fn parse_rpc_status(body: &str) -> Option<RpcStatus> {
+    let start = body.find(['{', '['])?;
+    let value: Value = serde_json::from_str(&body[start..]).ok()?;
+    let envelope = match value {
+        Value::Array(mut items) if !items.is_empty() => items.swap_remove(0),
+        value => value,
+    };
+    serde_json::from_value(envelope.get("error")?.clone()).ok()
+}
+
+fn parse_list<T: for<'de> Deserialize<'de>>(value: &Value) -> Vec<T> {
+    serde_json::from_value(value.clone()).unwrap_or_default()
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// Processing state of an uploaded file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FileState {
+    #[default]
+    StateUnspecified,
+    /// Still being processed; not yet usable in requests
+    Processing,
+    /// Ready to be referenced from requests
+    Active,
+    /// Processing failed
+    Failed,
+}
+
+/// A file uploaded through the Files API, as returned by the API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct File {
+    /// Resource name, e.g. "files/abc-123"
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Size in bytes, as a decimal string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    /// RFC 3339 time after which the file is deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256_hash: Option<String>,
+    /// URI to send as `fileUri` in a file data part
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default)]
+    pub state: FileState,
+    /// Why processing failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<serde_json::Value>,
+}
+
+impl File {
+    /// Whether the file's expiration time has passed at `now`, in Unix milliseconds
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiration_time
+            .as_deref()
+            .and_then(parse_rfc3339_ms)
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Response from `files.create` and the final upload chunk
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadFileResponse {
+    pub file: File,
+}
+
+/// Response from `files.list`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFilesResponse {
+    #[serde(default)]
+    pub files: Vec<File>,
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+}
+
+/// Normalize a file id or resource name to the resource name
+pub fn file_name(name: &str) -> String {
+    if name.starts_with("files/") {
+        name.to_string()
+    } else {
+        format!("files/{}", name)
+    }
+}
+
+/// Parse an RFC 3339 timestamp such as "2025-01-15T10:20:30.123Z" into Unix milliseconds
+pub fn parse_rfc3339_ms(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.trim().split_once(['T', 't'])?;
+
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    // Split off the zone: "Z" or a "+hh:mm"/"-hh:mm" offset
+    let (clock, offset_secs) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let at = time.rfind(['+', '-'])?;
+        let (hours, minutes) = time[at + 1..].split_once(':')?;
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        let sign = if time[at..].starts_with('-') { -1 } else { 1 };
+        (&time[..at], sign * offset)
+    };
+
+    let (whole, fraction) = clock.split_once('.').unwrap_or((clock, ""));
+    let mut hms = whole.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next()??);
+    let millis = format!("{:0<3}", fraction.get(..3).unwrap_or(fraction))
+        .parse::<i64>()
+        .ok()?;
+
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset_secs;
+    u64::try_from(secs * 1000 + millis).ok()
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -1,9 +1,21 @@
 use genai_types::{
     messages::{Role as GenaiRole, StopReason},
-    CompletionRequest, CompletionResponse, Message, MessageContent, ToolChoice, Usage,
+    CompletionRequest, CompletionResponse, Message, MessageContent, ModelPricing, ToolChoice,
+    Usage,
 };
-use mcp_protocol::tool::Tool as McpTool;
+use crate::cache::stable_hash;
+use crate::types::cached_content::CachedContent;
+use crate::types::files::File;
+use crate::usage::ModelUsage;
+use crate::types::schema::to_gemini_schema;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use mcp_protocol::tool::{Tool as McpTool, ToolContent};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Prefix of the ids the proxy invents for function calls Gemini returns without one
+pub const SYNTHETIC_CALL_ID_PREFIX: &str = "proxy-call-";
 
 /// Represents an error from the Gemini API
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +40,24 @@ pub enum GeminiError {
 
     /// Serialization error
     SerializationError(String),
+
+    /// The request's overall deadline passed, or would pass before the next retry
+    Timeout { timeout_ms: u32 },
+
+    /// A locally configured rate limit for the model is used up
+    RateLimited { model: String, retry_after_ms: u64 },
+
+    /// The prompt was blocked by safety filters before any candidate was generated
+    PromptBlocked {
+        block_reason: String,
+        safety_ratings: Vec<SafetyRating>,
+    },
+
+    /// The generated candidate was blocked by safety filters
+    ResponseBlocked {
+        finish_reason: FinishReason,
+        safety_ratings: Vec<SafetyRating>,
+    },
 }
 
 impl From<serde_json::Error> for GeminiError {
@@ -37,11 +67,12 @@ impl From<serde_json::Error> for GeminiError {
 }
 
 /// Role in a conversation (user or model)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub enum Role {
     #[serde(rename = "user")]
     User,
 
+    #[default]
     #[serde(rename = "model")]
     Model,
 
@@ -69,12 +100,6 @@ impl From<Role> for GenaiRole {
     }
 }
 
-impl Default for Role {
-    fn default() -> Self {
-        Role::Model
-    }
-}
-
 /// Represents inline media bytes.
 /// The spec mentions needing an IANA MIME type when this is used.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -82,11 +107,31 @@ impl Default for Role {
 pub struct Blob {
     /// The IANA MIME type of the media (e.g., "image/png", "application/pdf").
     pub mime_type: String,
-    /// The raw byte data.
-    #[serde(with = "serde_bytes")] // Use serde_bytes for efficient byte handling
+    /// The raw byte data, base64-encoded on the wire.
+    #[serde(with = "base64_bytes")]
     pub data: Vec<u8>,
 }
 
+/// Serde for bytes carried as a base64 string, as protobuf JSON `bytes` fields are
+mod base64_bytes {
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        // Protobuf JSON accepts both the standard and the URL-safe alphabet
+        STANDARD
+            .decode(&encoded)
+            .or_else(|_| URL_SAFE.decode(&encoded))
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 /// Represents a predicted function call from the model.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -130,8 +175,8 @@ pub struct FileData {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecutableCode {
-    /// The language of the code (e.g., "python", "javascript").
-    pub language: String, // Consider using an enum if languages are fixed
+    /// The language of the code; the code execution tool only runs "PYTHON".
+    pub language: String,
     /// The code string.
     pub code: String,
 }
@@ -140,11 +185,13 @@ pub struct ExecutableCode {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct CodeExecutionResult {
-    /// The outcome of the execution (e.g., "SUCCESS", "ERROR").
-    pub outcome: String, // Consider using an enum: enum Outcome { Success, Error }
-    /// The output from the execution (e.g., stdout, stderr, or specific result).
+    /// The outcome of the execution: "OUTCOME_OK", "OUTCOME_FAILED" or
+    /// "OUTCOME_DEADLINE_EXCEEDED".
+    pub outcome: String,
+    /// Stdout on success, otherwise stderr or another description of the failure.
+    /// Omitted when there was no output.
+    #[serde(default)]
     pub output: String,
-    // Potentially add more structured fields depending on needs, e.g., exit_code: Option<i32>
 }
 
 // --- The main Part Data Enum (representing the Union type) ---
@@ -190,6 +237,78 @@ pub struct Part {
     pub data: PartData,
 }
 
+impl Part {
+    /// Whether this part is a thought summary rather than part of the answer
+    pub fn is_thought(&self) -> bool {
+        self.thought.unwrap_or(false)
+    }
+
+    /// Whether this part is code run by the code execution tool or its result
+    pub fn is_code_execution(&self) -> bool {
+        matches!(
+            self.data,
+            PartData::ExecutableCode(_) | PartData::CodeExecutionResult(_)
+        )
+    }
+
+    /// Build the `functionResponse` part answering the call `tool_use_id` to `name`.
+    ///
+    /// Every content item is kept: a single item becomes the value itself and several
+    /// become an array, under `error` when the tool failed and `output` otherwise.
+    pub fn tool_result(
+        tool_use_id: String,
+        name: String,
+        content: Vec<ToolContent>,
+        is_error: bool,
+    ) -> Result<Vec<Self>, GeminiError> {
+        // Images and audio follow the response as media parts, which the model can see
+        let mut media = Vec::new();
+        let mut values = content
+            .into_iter()
+            .map(|item| match item {
+                ToolContent::Text { text } => Ok(serde_json::Value::String(text)),
+                ToolContent::Image { data, mime_type } | ToolContent::Audio { data, mime_type } => {
+                    let value = serde_json::Value::String(format!("<{} attached>", mime_type));
+                    media.push(Part::inline_data(mime_type, &data)?);
+                    Ok(value)
+                }
+                other => serde_json::to_value(other)
+                    .map_err(|e| GeminiError::SerializationError(format!("{:?}", e))),
+            })
+            .collect::<Result<Vec<serde_json::Value>, GeminiError>>()?;
+
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+        let key = if is_error { "error" } else { "output" };
+
+        let response = Part {
+            thought: None,
+            data: PartData::FunctionResponse(FunctionResponse {
+                id: api_call_id(tool_use_id),
+                name,
+                response: serde_json::json!({ key: value }),
+            }),
+        };
+        Ok(std::iter::once(response).chain(media).collect())
+    }
+
+    /// A media part from base64-encoded bytes
+    pub fn inline_data(mime_type: String, data: &str) -> Result<Self, GeminiError> {
+        let data = STANDARD
+            .decode(data.trim())
+            .map_err(|e| {
+                GeminiError::InvalidRequest(format!("Invalid base64 data for {}: {}", mime_type, e))
+            })?;
+        Ok(Part {
+            thought: None,
+            data: PartData::InlineData(Blob { mime_type, data }),
+        })
+    }
+}
+
 impl TryFrom<MessageContent> for Part {
     type Error = GeminiError;
 
@@ -202,31 +321,80 @@ impl TryFrom<MessageContent> for Part {
             MessageContent::ToolUse { name, input, id } => Ok(Part {
                 thought: None,
                 data: PartData::FunctionCall(FunctionCall {
-                    id: Some(id),
+                    id: api_call_id(id),
                     name,
                     args: input,
                 }),
             }),
-            // This is hacky and I would be surprised if it worked. I need to update the tool use
-            // to retain the name and I am not sure about the way we are serializing the
-            // ToolContent to json, but this is what I am going with for now.
-            MessageContent::ToolResult {
-                tool_use_id,
-                content,
-                is_error: _,
-            } => Ok(Part {
-                thought: None,
-                data: PartData::FunctionResponse(FunctionResponse {
-                    id: Some(tool_use_id),
-                    name: "none".to_string(),
-                    response: serde_json::to_value(content[0].clone())
-                        .map_err(|e| GeminiError::SerializationError(format!("{:?}", e)))?,
-                }),
-            }),
+            // Gemini matches responses to calls by function name, which only the
+            // conversation history knows; see `contents_from_messages`
+            MessageContent::ToolResult { tool_use_id, .. } => {
+                Err(GeminiError::InvalidRequest(format!(
+                    "Tool result {} cannot be converted without the tool call it answers",
+                    tool_use_id
+                )))
+            }
         }
     }
 }
 
+/// The id to send Gemini for a call: ids the proxy synthesized were never Gemini's,
+/// so they are left out, as are empty ones
+fn api_call_id(id: String) -> Option<String> {
+    if id.is_empty() || id.starts_with(SYNTHETIC_CALL_ID_PREFIX) {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+/// Convert a conversation, resolving the function name of each tool result from the
+/// `ToolUse` with the same id earlier in the history.
+pub fn contents_from_messages(messages: Vec<Message>) -> Result<Vec<Content>, GeminiError> {
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    let mut contents = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        let role = message.role.into();
+        let mut parts = Vec::with_capacity(message.content.len());
+
+        for item in message.content {
+            let part = match item {
+                MessageContent::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } => {
+                    let name = call_names.get(&tool_use_id).cloned().ok_or_else(|| {
+                        GeminiError::InvalidRequest(format!(
+                            "Tool result {} does not match any earlier tool call",
+                            tool_use_id
+                        ))
+                    })?;
+                    parts.extend(Part::tool_result(
+                        tool_use_id,
+                        name,
+                        content,
+                        is_error.unwrap_or(false),
+                    )?);
+                    continue;
+                }
+                other => {
+                    if let MessageContent::ToolUse { id, name, .. } = &other {
+                        call_names.insert(id.clone(), name.clone());
+                    }
+                    other.try_into()?
+                }
+            };
+            parts.push(part);
+        }
+
+        contents.push(Content { role, parts });
+    }
+
+    Ok(contents)
+}
+
 impl TryFrom<Part> for MessageContent {
     type Error = GeminiError;
 
@@ -274,31 +442,44 @@ impl TryFrom<Part> for MessageContent {
 }
 
 /// Content from Gemini API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Content {
     pub parts: Vec<Part>,
     #[serde(default)]
     pub role: Role,
 }
 
+/// A lone message has no history to find the name of the call a tool result answers
+/// in, so its tool results are named "none" and can only be matched by call id.
+/// Whole conversations should go through `contents_from_messages` instead.
 impl TryFrom<Message> for Content {
     type Error = GeminiError;
     fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let mut parts = Vec::with_capacity(message.content.len());
+        for item in message.content {
+            match item {
+                MessageContent::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } => parts.extend(Part::tool_result(
+                    tool_use_id,
+                    "none".to_string(),
+                    content,
+                    is_error.unwrap_or(false),
+                )?),
+                other => parts.push(other.try_into().map_err(|e| {
+                    GeminiError::SerializationError(format!(
+                        "Failed to convert message part: {:?}",
+                        e
+                    ))
+                })?),
+            }
+        }
+
         Ok(Content {
             role: message.role.into(),
-            parts: message
-                .content
-                .into_iter()
-                .map(|part| {
-                    part.try_into().map_err(|e| {
-                        GeminiError::SerializationError(format!(
-                            "Failed to convert message part: {:?}",
-                            e
-                        ))
-                    })
-                })
-                .collect::<Result<Vec<Part>, GeminiError>>()
-                .map_err(|e| GeminiError::SerializationError(format!("{:?}", e)))?,
+            parts,
         })
     }
 }
@@ -342,6 +523,33 @@ pub struct GenerationConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequences: Option<Vec<String>>,
+
+    /// Output MIME type, e.g. "application/json" or "text/x.enum"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+
+    /// Output schema in Gemini's OpenAPI subset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+
+    /// Output schema as standard JSON Schema, used instead of `response_schema`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_json_schema: Option<serde_json::Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<ThinkingConfig>,
+}
+
+/// Reasoning configuration for thinking models (Gemini 2.5 and later)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ThinkingConfig {
+    /// Tokens the model may spend thinking: 0 disables thinking, -1 lets the model decide
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<i32>,
+
+    /// Return thought summaries alongside the answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
 }
 
 /// Function declaration for Gemini tools
@@ -355,11 +563,64 @@ pub struct FunctionDeclaration {
     pub response: Option<serde_json::Value>,
 }
 
-/// Tool type for Gemini API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Tool type for Gemini API. Each entry sets one kind of tool: function
+/// declarations or one of the built-in tools.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Tool {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub function_declarations: Option<Vec<FunctionDeclaration>>,
+
+    /// Ground answers in Google Search results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<GoogleSearch>,
+
+    /// Let the model write and run Python in a sandbox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_execution: Option<CodeExecution>,
+
+    /// Let the model read the URLs given in the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_context: Option<UrlContext>,
+}
+
+/// The Google Search tool; it takes no settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GoogleSearch {}
+
+/// The code execution tool; it takes no settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CodeExecution {}
+
+/// The URL context tool; it takes no settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UrlContext {}
+
+/// A tool built into Gemini, enabled by name in `CompletionOptions`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinTool {
+    GoogleSearch,
+    CodeExecution,
+    UrlContext,
+}
+
+impl From<BuiltinTool> for Tool {
+    fn from(tool: BuiltinTool) -> Self {
+        match tool {
+            BuiltinTool::GoogleSearch => Tool {
+                google_search: Some(GoogleSearch {}),
+                ..Default::default()
+            },
+            BuiltinTool::CodeExecution => Tool {
+                code_execution: Some(CodeExecution {}),
+                ..Default::default()
+            },
+            BuiltinTool::UrlContext => Tool {
+                url_context: Some(UrlContext {}),
+                ..Default::default()
+            },
+        }
+    }
 }
 
 impl TryFrom<McpTool> for FunctionDeclaration {
@@ -433,6 +694,14 @@ pub struct GenerateContentRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_config: Option<ToolConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+
+    /// Name of a cached content holding the start of the prompt. The system
+    /// instruction, tools and tool config must then come from the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
 }
 
 impl TryFrom<CompletionRequest> for GenerateContentRequest {
@@ -455,9 +724,7 @@ impl TryFrom<CompletionRequest> for GenerateContentRequest {
         let generation_config = Some(GenerationConfig {
             temperature: request.temperature,
             max_output_tokens: Some(request.max_tokens),
-            top_p: None,
-            top_k: None,
-            stop_sequences: None,
+            ..Default::default()
         });
 
         // Handle tools
@@ -470,6 +737,7 @@ impl TryFrom<CompletionRequest> for GenerateContentRequest {
 
                 Some(vec![Tool {
                     function_declarations: Some(function_declarations),
+                    ..Default::default()
                 }])
             } else {
                 None
@@ -506,38 +774,280 @@ impl TryFrom<CompletionRequest> for GenerateContentRequest {
 
         Ok(GenerateContentRequest {
             model: request.model,
-            contents: request
-                .messages
-                .into_iter()
-                .map(|msg| msg.try_into())
-                .collect::<Result<Vec<Content>, GeminiError>>()
-                .map_err(|e| {
-                    GeminiError::SerializationError(format!("Failed to convert message: {:?}", e))
-                })?,
+            contents: contents_from_messages(request.messages)?,
             generation_config,
             system_instruction: system_instruction.flatten(),
             tools,
             tool_config,
+            safety_settings: None,
+            cached_content: None,
         })
     }
 }
 
+/// Gemini-specific options for a completion request that `CompletionRequest` cannot express
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CompletionOptions {
+    /// Output MIME type; defaults to "application/json" when a schema is given
+    #[serde(default)]
+    pub response_mime_type: Option<String>,
+
+    /// Standard JSON Schema for the output, rewritten into Gemini's OpenAPI subset
+    #[serde(default)]
+    pub response_schema: Option<serde_json::Value>,
+
+    /// JSON Schema passed to Gemini verbatim as `responseJsonSchema`
+    #[serde(default)]
+    pub response_json_schema: Option<serde_json::Value>,
+
+    /// Thinking budget and whether to return thought summaries
+    #[serde(default)]
+    pub thinking: Option<ThinkingConfig>,
+
+    /// Per-category thresholds overriding the actor's default safety settings
+    #[serde(default)]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+
+    /// Force caching on or off; by default only temperature-0 requests are cached
+    #[serde(default)]
+    pub cache: Option<bool>,
+
+    /// Only use the requested model, never its configured fallbacks
+    #[serde(default)]
+    pub disable_fallback: bool,
+
+    /// Media added to the last user message
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+
+    /// Cached content holding the start of the conversation, by id or name
+    #[serde(default)]
+    pub cached_content: Option<String>,
+
+    /// Built-in tools offered to the model alongside the request's own tools
+    #[serde(default)]
+    pub builtin_tools: Vec<BuiltinTool>,
+}
+
+/// Media sent along with a completion request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    /// Base64-encoded bytes sent inline with the request
+    Inline { mime_type: String, data: String },
+    /// A file referenced by URI, such as a Files API or `gs://` URI
+    File { mime_type: String, file_uri: String },
+}
+
+impl TryFrom<Attachment> for Part {
+    type Error = GeminiError;
+
+    fn try_from(attachment: Attachment) -> Result<Self, GeminiError> {
+        match attachment {
+            Attachment::Inline { mime_type, data } => Part::inline_data(mime_type, &data),
+            Attachment::File {
+                mime_type,
+                file_uri,
+            } => Ok(Part {
+                thought: None,
+                data: PartData::FileData(FileData {
+                    mime_type,
+                    file_uri,
+                }),
+            }),
+        }
+    }
+}
+
+impl GenerateContentRequest {
+    /// Convert a completion request and apply the Gemini-specific options on top
+    pub fn from_completion(
+        request: CompletionRequest,
+        options: CompletionOptions,
+    ) -> Result<Self, GeminiError> {
+        let mut converted = GenerateContentRequest::try_from(request)?;
+        let config = converted.generation_config.get_or_insert_with(Default::default);
+
+        if options.response_schema.is_some() && options.response_json_schema.is_some() {
+            return Err(GeminiError::InvalidRequest(
+                "Only one of response_schema and response_json_schema may be set".to_string(),
+            ));
+        }
+
+        let has_schema = options.response_schema.is_some() || options.response_json_schema.is_some();
+        config.response_mime_type = match options.response_mime_type {
+            Some(mime_type) => Some(mime_type),
+            None if has_schema => Some("application/json".to_string()),
+            None => None,
+        };
+
+        if has_schema {
+            let mime_type = config.response_mime_type.as_deref().unwrap_or_default();
+            if mime_type != "application/json" && mime_type != "text/x.enum" {
+                return Err(GeminiError::InvalidRequest(format!(
+                    "A response schema requires an application/json or text/x.enum MIME type, got {}",
+                    mime_type
+                )));
+            }
+        }
+
+        config.response_schema = options
+            .response_schema
+            .as_ref()
+            .map(to_gemini_schema)
+            .transpose()?;
+        config.response_json_schema = options.response_json_schema;
+        config.thinking_config = options.thinking;
+        converted.safety_settings = options.safety_settings;
+        converted.cached_content = options.cached_content;
+        if !options.builtin_tools.is_empty() {
+            converted
+                .tools
+                .get_or_insert_with(Vec::new)
+                .extend(options.builtin_tools.into_iter().map(Tool::from));
+        }
+
+        if !options.attachments.is_empty() {
+            let media = options
+                .attachments
+                .into_iter()
+                .map(Part::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            match converted
+                .contents
+                .iter_mut()
+                .rev()
+                .find(|content| matches!(content.role, Role::User))
+            {
+                Some(content) => content.parts.extend(media),
+                None => converted.contents.push(Content {
+                    role: Role::User,
+                    parts: media,
+                }),
+            }
+        }
+
+        Ok(converted)
+    }
+}
+
+/// Request body for `models/{model}:countTokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountTokensRequest {
+    pub generate_content_request: GenerateContentRequest,
+}
+
+impl From<GenerateContentRequest> for CountTokensRequest {
+    fn from(mut request: GenerateContentRequest) -> Self {
+        // The embedded request names its model by resource name
+        if !request.model.starts_with("models/") {
+            request.model = format!("models/{}", request.model);
+        }
+        CountTokensRequest {
+            generate_content_request: request,
+        }
+    }
+}
+
+/// Response from `models/{model}:countTokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountTokensResponse {
+    #[serde(default)]
+    pub total_tokens: u32,
+    #[serde(default)]
+    pub cached_content_token_count: Option<u32>,
+}
+
+/// Intended downstream use of an embedding
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TaskType {
+    TaskTypeUnspecified,
+    RetrievalQuery,
+    RetrievalDocument,
+    SemanticSimilarity,
+    Classification,
+    Clustering,
+    QuestionAnswering,
+    FactVerification,
+    CodeRetrievalQuery,
+}
+
+/// Request body for `models/{model}:embedContent`, also used per item in a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbedContentRequest {
+    /// Resource name of the model; only set inside a batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    pub content: Content,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_type: Option<TaskType>,
+
+    /// Only applicable with `TaskType::RetrievalDocument`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dimensionality: Option<u32>,
+}
+
+/// Request body for `models/{model}:batchEmbedContents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEmbedContentsRequest {
+    pub requests: Vec<EmbedContentRequest>,
+}
+
+/// A single embedding vector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentEmbedding {
+    #[serde(default)]
+    pub values: Vec<f32>,
+}
+
+/// Response from `models/{model}:embedContent`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedContentResponse {
+    pub embedding: ContentEmbedding,
+}
+
+/// Response from `models/{model}:batchEmbedContents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEmbedContentsResponse {
+    #[serde(default)]
+    pub embeddings: Vec<ContentEmbedding>,
+}
+
 /// Usage metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageMetadata {
+    #[serde(default)]
     pub prompt_token_count: u32,
+    #[serde(default)]
     pub candidates_token_count: u32,
+    #[serde(default)]
     pub total_token_count: u32,
+    /// Prompt tokens served from a context cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u32>,
+    /// Tokens spent on reasoning by thinking models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<u32>,
 }
 
 impl TryFrom<UsageMetadata> for Usage {
     type Error = GeminiError;
 
     fn try_from(usage: UsageMetadata) -> Result<Self, Self::Error> {
+        // Thinking tokens are billed as output, so they count towards output usage
         Ok(genai_types::Usage {
             input_tokens: usage.prompt_token_count,
-            output_tokens: usage.candidates_token_count,
+            output_tokens: usage.candidates_token_count + usage.thoughts_token_count.unwrap_or(0),
         })
     }
 }
@@ -546,17 +1056,116 @@ impl TryFrom<UsageMetadata> for Usage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    /// Blocked candidates may omit the content entirely
+    #[serde(default)]
     pub content: Content,
+    #[serde(default)]
     pub finish_reason: FinishReason,
     #[serde(default)]
     pub index: u32,
     #[serde(default)]
     pub safety_ratings: Vec<SafetyRating>,
+    /// Search queries and sources behind an answer grounded with Google Search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GroundingMetadata>,
+    /// The URLs the URL context tool retrieved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_context_metadata: Option<UrlContextMetadata>,
+}
+
+/// How an answer was grounded in Google Search results
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingMetadata {
+    /// The queries the model searched for
+    #[serde(default)]
+    pub web_search_queries: Vec<String>,
+    /// The sources found
+    #[serde(default)]
+    pub grounding_chunks: Vec<GroundingChunk>,
+    /// Which parts of the answer each source supports
+    #[serde(default)]
+    pub grounding_supports: Vec<GroundingSupport>,
+    /// Search suggestions to display with the answer, as required by the terms of use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_entry_point: Option<SearchEntryPoint>,
+}
+
+/// A source used to ground an answer
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct GroundingChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebSource>,
+}
+
+/// A web page used as a source
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct WebSource {
+    #[serde(default)]
+    pub uri: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// A segment of the answer and the sources that support it
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingSupport {
+    #[serde(default)]
+    pub segment: Segment,
+    /// Indices into `grounding_chunks`
+    #[serde(default)]
+    pub grounding_chunk_indices: Vec<u32>,
+    #[serde(default)]
+    pub confidence_scores: Vec<f32>,
+}
+
+/// A span of the answer text, as byte offsets into its part
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    #[serde(default)]
+    pub part_index: u32,
+    #[serde(default)]
+    pub start_index: u32,
+    #[serde(default)]
+    pub end_index: u32,
+    #[serde(default)]
+    pub text: String,
+}
+
+/// Google Search suggestions for the answer
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchEntryPoint {
+    /// HTML and CSS snippet that renders the suggestions
+    #[serde(default)]
+    pub rendered_content: String,
+}
+
+/// The URLs retrieved by the URL context tool
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlContextMetadata {
+    #[serde(default)]
+    pub url_metadata: Vec<UrlMetadata>,
+}
+
+/// Whether one URL could be retrieved
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlMetadata {
+    #[serde(default)]
+    pub retrieved_url: String,
+    /// Such as "URL_RETRIEVAL_STATUS_SUCCESS" or "URL_RETRIEVAL_STATUS_ERROR"
+    #[serde(default)]
+    pub url_retrieval_status: String,
 }
 
 /// Finish reason for the a candidate
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub enum FinishReason {
+    #[default]
     #[serde(rename = "FINISH_REASON_UNSPECIFIED")]
     FinishReasonUnspecified,
 
@@ -594,6 +1203,20 @@ pub enum FinishReason {
     ImageSafety,
 }
 
+impl FinishReason {
+    /// Whether the candidate was stopped by a safety or content filter
+    pub fn is_blocked(&self) -> bool {
+        matches!(
+            self,
+            FinishReason::Safety
+                | FinishReason::Blocklist
+                | FinishReason::ProhibitedContent
+                | FinishReason::Spii
+                | FinishReason::ImageSafety
+        )
+    }
+}
+
 impl From<FinishReason> for StopReason {
     fn from(reason: FinishReason) -> Self {
         match reason {
@@ -601,17 +1224,74 @@ impl From<FinishReason> for StopReason {
             FinishReason::Stop => StopReason::EndTurn,
             FinishReason::MaxTokens => StopReason::MaxTokens,
             _ => StopReason::Other(
-                serde_json::to_string(&reason).unwrap_or_else(|_| "Unknown".to_string()),
+                serde_json::to_value(&reason)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_else(|| "Unknown".to_string()),
             ),
         }
     }
 }
 
 /// Safety rating from Gemini API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SafetyRating {
     pub category: String,
     pub probability: String,
+    /// Whether this rating caused the content to be blocked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<bool>,
+}
+
+/// Harm categories that can be filtered with a `SafetySetting`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+}
+
+/// Probability at and above which content in a category is blocked
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmBlockThreshold {
+    HarmBlockThresholdUnspecified,
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+    Off,
+}
+
+/// Blocking threshold for one harm category
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: HarmBlockThreshold,
+}
+
+/// Combine actor-wide default safety settings with per-request overrides.
+///
+/// A request setting replaces the default for the same category; defaults for
+/// other categories still apply.
+pub fn merge_safety_settings(
+    defaults: &[SafetySetting],
+    overrides: &[SafetySetting],
+) -> Vec<SafetySetting> {
+    let mut merged: Vec<SafetySetting> = defaults
+        .iter()
+        .filter(|d| !overrides.iter().any(|o| o.category == d.category))
+        .cloned()
+        .collect();
+    merged.extend(overrides.iter().cloned());
+    merged
 }
 
 /// Response from Gemini API
@@ -627,7 +1307,21 @@ pub struct GenerateContentResponse {
 impl TryFrom<GenerateContentResponse> for CompletionResponse {
     type Error = GeminiError;
 
-    fn try_from(response: GenerateContentResponse) -> Result<Self, Self::Error> {
+    fn try_from(mut response: GenerateContentResponse) -> Result<Self, Self::Error> {
+        response.assign_call_ids();
+
+        // A blocked prompt comes back without candidates
+        if let Some(PromptFeedback {
+            block_reason: Some(block_reason),
+            safety_ratings,
+        }) = &response.prompt_feedback
+        {
+            return Err(GeminiError::PromptBlocked {
+                block_reason: block_reason.clone(),
+                safety_ratings: safety_ratings.clone(),
+            });
+        }
+
         match response.candidates {
             Some(candidates) => {
                 // We are only interested in the first candidate for now
@@ -639,19 +1333,26 @@ impl TryFrom<GenerateContentResponse> for CompletionResponse {
 
                 let candidate = candidates[0].clone();
 
-                // Convert all parts in the candidate to MessageContent
+                if candidate.finish_reason.is_blocked() {
+                    return Err(GeminiError::ResponseBlocked {
+                        finish_reason: candidate.finish_reason,
+                        safety_ratings: candidate.safety_ratings,
+                    });
+                }
+
+                // Convert all answer parts in the candidate to MessageContent; thought
+                // summaries and code execution are not answer, and are reported
+                // separately
                 let content_parts = candidate
                     .content
                     .parts
                     .iter()
+                    .filter(|part| !part.is_thought() && !part.is_code_execution())
                     .map(|part| (*part).clone().try_into())
                     .collect::<Result<Vec<MessageContent>, GeminiError>>()?;
 
                 let usage = match response.usage_metadata {
-                    Some(usage) => Usage {
-                        input_tokens: usage.prompt_token_count,
-                        output_tokens: usage.candidates_token_count,
-                    },
+                    Some(usage) => usage.try_into()?,
                     None => Usage {
                         input_tokens: 0,
                         output_tokens: 0,
@@ -681,38 +1382,239 @@ impl TryFrom<GenerateContentResponse> for CompletionResponse {
                     usage,
                 })
             }
-            None => {
-                return Err(GeminiError::InvalidResponse(format!(
-                    "No candidates in response. Response: {:?}",
-                    response
-                )));
+            None => Err(GeminiError::InvalidResponse(format!(
+                "No candidates in response. Response: {:?}",
+                response
+            ))),
+        }
+    }
+}
+
+impl GenerateContentResponse {
+    /// Give every function call without an id a synthetic one, so parallel calls can
+    /// be told apart.
+    ///
+    /// Ids are `proxy-call-{response hash}-{part index}`: deterministic for a given
+    /// response and unique within it. They are stripped again when the calls and their
+    /// results are sent back to Gemini.
+    pub fn assign_call_ids(&mut self) {
+        let hash = match serde_json::to_vec(&*self) {
+            Ok(bytes) => stable_hash(&bytes),
+            Err(_) => return,
+        };
+
+        let parts = self
+            .candidates
+            .iter_mut()
+            .flatten()
+            .flat_map(|candidate| candidate.content.parts.iter_mut());
+
+        for (index, part) in parts.enumerate() {
+            if let PartData::FunctionCall(call) = &mut part.data {
+                if call.id.as_deref().unwrap_or_default().is_empty() {
+                    call.id = Some(format!(
+                        "{}{:016x}-{}",
+                        SYNTHETIC_CALL_ID_PREFIX, hash, index
+                    ));
+                }
             }
         }
     }
 }
 
+/// Gemini-specific details of a completion that `CompletionResponse` cannot carry
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CompletionDetails {
+    /// Thought summaries, in order, kept apart from the answer
+    pub thoughts: Vec<String>,
+
+    /// Full usage as reported by Gemini, including thinking tokens
+    pub usage: Option<UsageMetadata>,
+
+    /// Media returned in the answer, which the completion only names by MIME type
+    #[serde(default)]
+    pub media: Vec<Blob>,
+
+    /// Code the model ran with the code execution tool and the results, in order,
+    /// as `executableCode` and `codeExecutionResult` parts
+    #[serde(default)]
+    pub code_execution: Vec<PartData>,
+
+    /// Sources behind an answer grounded with Google Search
+    #[serde(default)]
+    pub grounding: Option<GroundingMetadata>,
+
+    /// URLs read by the URL context tool
+    #[serde(default)]
+    pub url_context: Option<UrlContextMetadata>,
+}
+
+impl From<&GenerateContentResponse> for CompletionDetails {
+    fn from(response: &GenerateContentResponse) -> Self {
+        let thoughts = response
+            .candidates
+            .iter()
+            .flat_map(|candidates| candidates.first())
+            .flat_map(|candidate| candidate.content.parts.iter())
+            .filter(|part| part.is_thought())
+            .filter_map(|part| match &part.data {
+                PartData::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let media = response
+            .candidates
+            .iter()
+            .flat_map(|candidates| candidates.first())
+            .flat_map(|candidate| candidate.content.parts.iter())
+            .filter_map(|part| match &part.data {
+                PartData::InlineData(blob) if !part.is_thought() => Some(blob.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let code_execution = response
+            .candidates
+            .iter()
+            .flat_map(|candidates| candidates.first())
+            .flat_map(|candidate| candidate.content.parts.iter())
+            .filter(|part| part.is_code_execution())
+            .map(|part| part.data.clone())
+            .collect();
+
+        let candidate = response
+            .candidates
+            .as_ref()
+            .and_then(|candidates| candidates.first());
+
+        CompletionDetails {
+            thoughts,
+            usage: response.usage_metadata.clone(),
+            media,
+            code_execution,
+            grounding: candidate.and_then(|c| c.grounding_metadata.clone()),
+            url_context: candidate.and_then(|c| c.url_context_metadata.clone()),
+        }
+    }
+}
+
 /// Feedback on prompt
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PromptFeedback {
+    /// Why the prompt was blocked, if it was
+    #[serde(default)]
+    pub block_reason: Option<String>,
+    #[serde(default)]
     pub safety_ratings: Vec<SafetyRating>,
 }
 
 /// Request type for the Google Proxy
+///
+/// Gemini-specific operations that have no equivalent in `genai_types::ProxyRequest`.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum GeminiRequest {
     GenerateContent {
-        request: GenerateContentRequest,
+        request: Box<GenerateContentRequest>,
         model: String,
+        /// Ignored: the host cannot stream, so responses are always returned whole
         stream: bool,
+        /// Only use `model`, never its configured fallbacks
+        #[serde(default)]
+        disable_fallback: bool,
+    },
+    /// List models with Gemini's own model details
+    ListGeminiModels,
+    /// Generate a completion with Gemini-specific options
+    GenerateGeminiCompletion {
+        request: CompletionRequest,
+        #[serde(default)]
+        options: CompletionOptions,
+    },
+    /// Count the tokens a completion request would consume
+    CountTokens { request: CompletionRequest },
+    /// Embed one or more texts with a `text-embedding-*` model
+    Embed {
+        model: String,
+        texts: Vec<String>,
+        task_type: Option<TaskType>,
+        title: Option<String>,
+        output_dimensionality: Option<u32>,
+    },
+    /// Token usage and cost accumulated since the actor started
+    GetUsage,
+    /// Upload base64-encoded bytes through the Files API
+    UploadFile {
+        data: String,
+        mime_type: String,
+        #[serde(default)]
+        display_name: Option<String>,
+        /// Poll until the file is ACTIVE before responding
+        #[serde(default)]
+        wait_until_active: bool,
+    },
+    GetFile { name: String },
+    ListFiles {
+        #[serde(default)]
+        page_size: Option<u32>,
+        #[serde(default)]
+        page_token: Option<String>,
     },
-    ListModels,
+    DeleteFile { name: String },
+    /// Poll a file until it is ACTIVE
+    WaitForFile { name: String },
+    /// Delete the tracked files whose expiration time has passed
+    CleanUpFiles,
+    /// Cache a prompt prefix; `content.model` names the model it is for
+    CreateCachedContent { content: Box<CachedContent> },
+    GetCachedContent { name: String },
+    ListCachedContents {
+        #[serde(default)]
+        page_size: Option<u32>,
+        #[serde(default)]
+        page_token: Option<String>,
+    },
+    /// Give a cache a new time to live, counted from now
+    UpdateCachedContentTtl { name: String, ttl_secs: u64 },
+    DeleteCachedContent { name: String },
 }
 
 /// Response from Google Proxy
 #[derive(Debug, Serialize, Deserialize)]
 pub enum GeminiResponse {
     Content { content: GenerateContentResponse },
+    Completion {
+        completion: CompletionResponse,
+        details: CompletionDetails,
+    },
     ListModels { models: Vec<ModelInfo> },
+    TokenCount {
+        total_tokens: u32,
+        cached_content_token_count: Option<u32>,
+    },
+    /// One vector per input text, in input order
+    Embeddings { embeddings: Vec<Vec<f32>> },
+    /// Usage per model, plus the totals over all of them
+    Usage {
+        models: BTreeMap<String, ModelUsage>,
+        total: ModelUsage,
+    },
+    File { file: File },
+    Files {
+        files: Vec<File>,
+        next_page_token: Option<String>,
+    },
+    FileDeleted { name: String },
+    /// Names of the expired files deleted and removed from the actor state
+    FilesCleanedUp { names: Vec<String> },
+    CachedContent { cached_content: CachedContent },
+    CachedContents {
+        cached_contents: Vec<CachedContent>,
+        next_page_token: Option<String>,
+    },
+    CachedContentDeleted { name: String },
+    /// `error` is a JSON-encoded `ErrorReport`
     Error { error: String },
 }
 
@@ -728,6 +1630,9 @@ pub struct ModelInfo {
     pub temperature_range: Option<(f32, f32)>,
     pub top_p_range: Option<(f32, f32)>,
     pub top_k_range: Option<(u32, u32)>,
+    /// Prices from the configured price table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
 }
 
 impl From<ModelInfo> for genai_types::ModelInfo {
@@ -737,46 +1642,129 @@ impl From<ModelInfo> for genai_types::ModelInfo {
             display_name: model.display_name,
             provider: "google".to_string(),
             max_tokens: model.output_token_limit,
+            pricing: model.pricing,
+        }
+    }
+}
+
+/// A model entry as returned by the `GET /models` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiModel {
+    /// Resource name, e.g. "models/gemini-2.0-flash"
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_token_limit: u32,
+    #[serde(default)]
+    pub output_token_limit: u32,
+    #[serde(default)]
+    pub supported_generation_methods: Vec<String>,
+    /// Default sampling temperature
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_temperature: Option<f32>,
+    /// Default nucleus sampling value
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Default top-k value
+    #[serde(default)]
+    pub top_k: Option<u32>,
+}
+
+/// One page of the `GET /models` response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListModelsResponse {
+    #[serde(default)]
+    pub models: Vec<ApiModel>,
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+}
+
+impl From<ApiModel> for ModelInfo {
+    fn from(model: ApiModel) -> Self {
+        let id = model
+            .name
+            .strip_prefix("models/")
+            .unwrap_or(&model.name)
+            .to_string();
+
+        // The API reports defaults and maxima rather than ranges
+        let temperature_range = match (model.max_temperature, model.temperature) {
+            (Some(max), _) => Some((0.0, max)),
+            (None, Some(_)) => Some((0.0, 2.0)),
+            (None, None) => None,
+        };
+
+        ModelInfo {
+            display_name: model.display_name.unwrap_or_else(|| id.clone()),
+            id,
+            description: model.description,
+            input_token_limit: model.input_token_limit,
+            output_token_limit: model.output_token_limit,
+            supported_generation_methods: model.supported_generation_methods,
+            temperature_range,
+            top_p_range: model.top_p.map(|_| (0.0, 1.0)),
+            top_k_range: model.top_k.map(|top_k| (1, top_k)),
             pricing: None,
         }
     }
 }
 
 impl ModelInfo {
+    /// Built-in model list used when the `/models` endpoint cannot be reached
     pub fn get_default_models() -> Vec<ModelInfo> {
+        let methods = vec![
+            "generateContent".to_string(),
+            "countTokens".to_string(),
+            "streamGenerateContent".to_string(),
+        ];
+
         vec![
             ModelInfo {
-                id: "gemini-2.5-flash-preview-04-17".to_string(),
-                display_name: "Gemini 2.5 Flash".to_string(),
+                id: "gemini-2.5-pro".to_string(),
+                display_name: "Gemini 2.5 Pro".to_string(),
                 description: Some(
-                    "Optimized for speed, versatile on a broad range of tasks".to_string(),
+                    "Most capable model, with strong reasoning across complex tasks".to_string(),
                 ),
-                input_token_limit: 1048576,
-                output_token_limit: 65536,
-                supported_generation_methods: vec![
-                    "generateContent".to_string(),
-                    "streamGenerateContent".to_string(),
-                ],
+                input_token_limit: 1_048_576,
+                output_token_limit: 65_536,
+                supported_generation_methods: methods.clone(),
                 temperature_range: Some((0.0, 2.0)),
                 top_p_range: Some((0.0, 1.0)),
-                top_k_range: Some((1, 40)),
+                top_k_range: Some((1, 64)),
+                pricing: None,
             },
             ModelInfo {
-                id: "gemini-2.0-pro".to_string(),
-                display_name: "Gemini 2.0 Pro".to_string(),
+                id: "gemini-2.5-flash".to_string(),
+                display_name: "Gemini 2.5 Flash".to_string(),
                 description: Some(
-                    "High-quality model with strong reasoning across a variety of tasks"
-                        .to_string(),
+                    "Optimized for speed, versatile on a broad range of tasks".to_string(),
                 ),
-                input_token_limit: 32_000,
-                output_token_limit: 16_000,
-                supported_generation_methods: vec![
-                    "generateContent".to_string(),
-                    "streamGenerateContent".to_string(),
-                ],
+                input_token_limit: 1_048_576,
+                output_token_limit: 65_536,
+                supported_generation_methods: methods.clone(),
+                temperature_range: Some((0.0, 2.0)),
+                top_p_range: Some((0.0, 1.0)),
+                top_k_range: Some((1, 64)),
+                pricing: None,
+            },
+            ModelInfo {
+                id: "gemini-2.0-flash".to_string(),
+                display_name: "Gemini 2.0 Flash".to_string(),
+                description: Some("Fast, cost-efficient multimodal model".to_string()),
+                input_token_limit: 1_048_576,
+                output_token_limit: 8_192,
+                supported_generation_methods: methods,
                 temperature_range: Some((0.0, 2.0)),
                 top_p_range: Some((0.0, 1.0)),
                 top_k_range: Some((1, 40)),
+                pricing: None,
             },
         ]
     }
@@ -1,2 +1,6 @@
 pub mod state;
+pub mod cached_content;
+pub mod error;
+pub mod files;
 pub mod gemini;
+pub mod schema;
@@ -0,0 +1,207 @@
+use crate::types::gemini::GeminiError;
+use serde_json::{Map, Value};
+
+/// Keywords of Gemini's OpenAPI schema subset that are carried over unchanged
+const PASSTHROUGH_KEYWORDS: &[&str] = &[
+    "title",
+    "description",
+    "nullable",
+    "required",
+    "minItems",
+    "maxItems",
+    "minProperties",
+    "maxProperties",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "example",
+    "default",
+    "propertyOrdering",
+];
+
+/// String formats Gemini accepts; anything else is dropped
+const SUPPORTED_FORMATS: &[&str] = &["enum", "date-time", "int32", "int64", "float", "double"];
+
+/// How deep `$ref` chains may nest before we assume the schema is recursive
+const MAX_REF_DEPTH: usize = 16;
+
+/// Rewrite a standard JSON Schema into the OpenAPI subset accepted by `responseSchema`.
+///
+/// Local `$ref`s into `$defs`/`definitions` are inlined, `type: [T, "null"]` and
+/// `anyOf` with a null branch become `nullable`, `oneOf` becomes `anyOf`, `const`
+/// becomes a single-value `enum`, and keywords Gemini does not understand
+/// (`additionalProperties`, `$schema`, unsupported formats, ...) are dropped.
+pub fn to_gemini_schema(schema: &Value) -> Result<Value, GeminiError> {
+    convert(schema, schema, 0)
+}
+
+fn convert(schema: &Value, root: &Value, depth: usize) -> Result<Value, GeminiError> {
+    let object = match schema {
+        Value::Object(object) => object,
+        Value::Bool(true) => return Ok(Value::Object(Map::new())),
+        other => {
+            return Err(GeminiError::InvalidRequest(format!(
+                "Unsupported schema node: {}",
+                other
+            )))
+        }
+    };
+
+    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+        if depth >= MAX_REF_DEPTH {
+            return Err(GeminiError::InvalidRequest(format!(
+                "Schema reference {} is recursive, which Gemini does not support",
+                reference
+            )));
+        }
+        let target = resolve_ref(reference, root)?;
+        let mut resolved = convert(target, root, depth + 1)?;
+        // Sibling keywords such as `description` refine the referenced schema
+        if let (Value::Object(resolved), Value::Object(siblings)) =
+            (&mut resolved, convert(&strip_ref(object), root, depth)?)
+        {
+            resolved.extend(siblings);
+        }
+        return Ok(resolved);
+    }
+
+    let mut out = Map::new();
+
+    for key in PASSTHROUGH_KEYWORDS {
+        if let Some(value) = object.get(*key) {
+            out.insert(key.to_string(), value.clone());
+        }
+    }
+
+    match object.get("type") {
+        Some(Value::String(kind)) => {
+            out.insert("type".to_string(), Value::String(kind.to_uppercase()));
+        }
+        Some(Value::Array(kinds)) => {
+            let mut non_null = kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .filter(|k| *k != "null");
+            let kind = non_null.next();
+            if non_null.next().is_some() {
+                return Err(GeminiError::InvalidRequest(format!(
+                    "Schema type unions are not supported: {:?}",
+                    kinds
+                )));
+            }
+            if let Some(kind) = kind {
+                out.insert("type".to_string(), Value::String(kind.to_uppercase()));
+            }
+            if kinds.iter().any(|k| k == "null") {
+                out.insert("nullable".to_string(), Value::Bool(true));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(format) = object.get("format").and_then(Value::as_str) {
+        if SUPPORTED_FORMATS.contains(&format) {
+            out.insert("format".to_string(), Value::String(format.to_string()));
+        }
+    }
+
+    if let Some(values) = object.get("enum").and_then(Value::as_array) {
+        out.insert("enum".to_string(), Value::Array(enum_values(values)));
+        out.entry("type")
+            .or_insert(Value::String("STRING".to_string()));
+    } else if let Some(value) = object.get("const") {
+        out.insert(
+            "enum".to_string(),
+            Value::Array(enum_values(std::slice::from_ref(value))),
+        );
+        out.entry("type")
+            .or_insert(Value::String("STRING".to_string()));
+    }
+
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        let mut converted = Map::new();
+        for (name, property) in properties {
+            converted.insert(name.clone(), convert(property, root, depth)?);
+        }
+        out.insert("properties".to_string(), Value::Object(converted));
+        out.entry("type")
+            .or_insert(Value::String("OBJECT".to_string()));
+    }
+
+    match object.get("items") {
+        Some(Value::Array(items)) => {
+            if let Some(first) = items.first() {
+                out.insert("items".to_string(), convert(first, root, depth)?);
+            }
+        }
+        Some(items) => {
+            out.insert("items".to_string(), convert(items, root, depth)?);
+        }
+        None => {}
+    }
+
+    let variants = object
+        .get("anyOf")
+        .or_else(|| object.get("oneOf"))
+        .and_then(Value::as_array);
+    if let Some(variants) = variants {
+        let (nulls, others): (Vec<&Value>, Vec<&Value>) = variants
+            .iter()
+            .partition(|v| v.get("type").and_then(Value::as_str) == Some("null"));
+
+        if !nulls.is_empty() {
+            out.insert("nullable".to_string(), Value::Bool(true));
+        }
+
+        if others.len() == 1 {
+            // `anyOf: [T, null]` is just a nullable T
+            if let Value::Object(inner) = convert(others[0], root, depth)? {
+                for (key, value) in inner {
+                    out.entry(key).or_insert(value);
+                }
+            }
+        } else {
+            let converted = others
+                .into_iter()
+                .map(|v| convert(v, root, depth))
+                .collect::<Result<Vec<Value>, GeminiError>>()?;
+            out.insert("anyOf".to_string(), Value::Array(converted));
+        }
+    }
+
+    Ok(Value::Object(out))
+}
+
+/// Look up a local `#/$defs/...` or `#/definitions/...` reference
+fn resolve_ref<'a>(reference: &str, root: &'a Value) -> Result<&'a Value, GeminiError> {
+    let pointer = reference.strip_prefix('#').ok_or_else(|| {
+        GeminiError::InvalidRequest(format!(
+            "Only local schema references are supported, got {}",
+            reference
+        ))
+    })?;
+
+    root.pointer(pointer).ok_or_else(|| {
+        GeminiError::InvalidRequest(format!("Unresolved schema reference {}", reference))
+    })
+}
+
+fn strip_ref(object: &Map<String, Value>) -> Value {
+    let mut siblings = object.clone();
+    siblings.remove("$ref");
+    Value::Object(siblings)
+}
+
+/// Gemini enums are always strings
+fn enum_values(values: &[Value]) -> Vec<Value> {
+    values
+        .iter()
+        .filter(|v| !v.is_null())
+        .map(|v| match v {
+            Value::String(s) => Value::String(s.clone()),
+            other => Value::String(other.to_string()),
+        })
+        .collect()
+}
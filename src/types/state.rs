@@ -1,7 +1,17 @@
+use crate::api::auth::AccessToken;
+use crate::api::keys::KeyPool;
+use crate::cache::ResponseCache;
+use crate::logging::PayloadLogging;
+use crate::rate_limit::{RateLimit, RateLimitMode, RateLimiter};
+use crate::types::cached_content::{CachedPrefix, ContextCacheConfig};
+use crate::types::files::File;
+use crate::types::gemini::SafetySetting;
+use crate::usage::{ModelPrice, UsageLedger};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Configuration options for initialization (with optional fields)
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct InitConfig {
     /// The default Gemini model to use
     pub default_model: Option<String>,
@@ -14,6 +24,100 @@ pub struct InitConfig {
 
     /// Retry configuration for handling API errors
     pub retry_config: Option<RetryConfig>,
+
+    /// Default per-category safety thresholds, overridable per request
+    #[serde(default)]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+
+    /// Which Google API serves the requests
+    #[serde(default)]
+    pub backend: Option<Backend>,
+
+    /// Scheme and host to send requests to instead of the backend's own
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// API version to use instead of the backend's default
+    #[serde(default)]
+    pub api_version: Option<ApiVersion>,
+
+    /// Static headers added to every API request
+    #[serde(default)]
+    pub headers: Option<BTreeMap<String, String>>,
+
+    /// How much of request and response payloads is logged
+    #[serde(default)]
+    pub payload_logging: Option<PayloadLogging>,
+
+    /// Models to try, in order, when a model stays overloaded or out of quota
+    #[serde(default)]
+    pub fallback_models: Option<BTreeMap<String, Vec<String>>>,
+
+    /// Logical model names, such as "fast", mapped to concrete model ids
+    #[serde(default)]
+    pub model_aliases: Option<BTreeMap<String, String>>,
+
+    /// Local request and token limits per model
+    #[serde(default)]
+    pub rate_limits: Option<BTreeMap<String, RateLimit>>,
+
+    /// Whether requests over a local limit wait or fail
+    #[serde(default)]
+    pub rate_limit_mode: Option<RateLimitMode>,
+
+    /// Prices per model, used to cost the recorded usage
+    #[serde(default)]
+    pub prices: Option<BTreeMap<String, ModelPrice>>,
+
+    /// Cache large prompt prefixes automatically with the cachedContents API
+    #[serde(default)]
+    pub context_cache: Option<ContextCacheConfig>,
+}
+
+/// Version of the Gemini REST API
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    V1,
+    V1beta,
+    V1alpha,
+}
+
+impl ApiVersion {
+    /// The version's URL path segment
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V1beta => "v1beta",
+            ApiVersion::V1alpha => "v1alpha",
+        }
+    }
+}
+
+/// The Google API that serves Gemini requests
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Backend {
+    /// The Gemini Developer API, authenticated with `GEMINI_API_KEY`
+    #[default]
+    GoogleAi,
+
+    /// Vertex AI, authenticated with the service account in `GOOGLE_SERVICE_ACCOUNT_KEY`
+    VertexAi(VertexConfig),
+}
+
+/// Where Gemini runs on Vertex AI
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VertexConfig {
+    /// Google Cloud project ID
+    pub project: String,
+
+    /// Region such as "europe-west4", or "global"
+    pub location: String,
+
+    /// Overrides the token URI from the service-account key
+    #[serde(default)]
+    pub token_uri: Option<String>,
 }
 
 /// Configuration for retry logic
@@ -54,6 +158,69 @@ pub struct Config {
 
     /// Retry configuration for handling API errors
     pub retry_config: RetryConfig,
+
+    /// Default per-category safety thresholds, overridable per request
+    #[serde(default)]
+    pub safety_settings: Vec<SafetySetting>,
+
+    /// Which Google API serves the requests
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// Scheme and host to send requests to; the backend's own host if unset
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// API version; v1beta on the Gemini Developer API and v1 on Vertex AI if unset
+    #[serde(default)]
+    pub api_version: Option<ApiVersion>,
+
+    /// Static headers added to every API request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// How much of request and response payloads is logged
+    #[serde(default)]
+    pub payload_logging: PayloadLogging,
+
+    /// Models to try, in order, when a model stays overloaded or out of quota
+    #[serde(default)]
+    pub fallback_models: BTreeMap<String, Vec<String>>,
+
+    /// Logical model names, such as "fast", mapped to concrete model ids
+    #[serde(default)]
+    pub model_aliases: BTreeMap<String, String>,
+
+    /// Local request and token limits per model
+    #[serde(default)]
+    pub rate_limits: BTreeMap<String, RateLimit>,
+
+    /// Whether requests over a local limit wait or fail
+    #[serde(default)]
+    pub rate_limit_mode: RateLimitMode,
+
+    /// Prices per model, used to cost the recorded usage
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+
+    /// Cache large prompt prefixes automatically; off if unset
+    #[serde(default)]
+    pub context_cache: Option<ContextCacheConfig>,
+}
+
+impl Config {
+    /// The concrete model id for a requested model: `default_model` when the request
+    /// leaves it empty, then looked up in the alias table
+    pub fn resolve_model(&self, model: &str) -> String {
+        let model = match model.trim() {
+            "" => self.default_model.as_str(),
+            model => model,
+        };
+        self.model_aliases
+            .get(model)
+            .cloned()
+            .unwrap_or_else(|| model.to_string())
+    }
 }
 
 impl Default for Config {
@@ -63,6 +230,18 @@ impl Default for Config {
             max_cache_size: Some(100),
             timeout_ms: 30000, // 30 seconds
             retry_config: RetryConfig::default(),
+            safety_settings: vec![],
+            backend: Backend::GoogleAi,
+            base_url: None,
+            api_version: None,
+            headers: BTreeMap::new(),
+            payload_logging: PayloadLogging::Redacted,
+            fallback_models: BTreeMap::new(),
+            model_aliases: BTreeMap::new(),
+            rate_limits: BTreeMap::new(),
+            rate_limit_mode: RateLimitMode::Queue,
+            prices: BTreeMap::new(),
+            context_cache: None,
         }
     }
 }
@@ -73,20 +252,46 @@ pub struct State {
     /// Actor ID
     pub id: String,
 
-    /// Google API key
-    pub api_key: String,
+    /// Gemini Developer API keys, with their quota cooldowns
+    #[serde(alias = "api_key", deserialize_with = "KeyPool::deserialize_or_single")]
+    pub api_keys: KeyPool,
 
     /// Actor configuration
     pub config: Config,
 
     /// Store ID (if using runtime store)
     pub store_id: Option<String>,
+
+    /// LRU index of responses cached in the store
+    #[serde(default)]
+    pub cache: ResponseCache,
+
+    /// The current Vertex AI access token, reused until it expires. The service-account
+    /// key that mints it is read from the environment and never kept here.
+    #[serde(default)]
+    pub access_token: Option<AccessToken>,
+
+    /// Buckets enforcing the local rate limits
+    #[serde(default)]
+    pub rate_limiter: RateLimiter,
+
+    /// Token usage and cost per model
+    #[serde(default)]
+    pub usage: UsageLedger,
+
+    /// Files uploaded through the Files API, by resource name
+    #[serde(default)]
+    pub files: BTreeMap<String, File>,
+
+    /// Caches created for prompt prefixes, by prefix hash
+    #[serde(default)]
+    pub context_caches: BTreeMap<String, CachedPrefix>,
 }
 
 impl State {
     pub fn new(
         id: String,
-        api_key: String,
+        api_keys: Vec<String>,
         store_id: Option<String>,
         init_config: Option<InitConfig>,
     ) -> Self {
@@ -97,15 +302,33 @@ impl State {
                 max_cache_size: init.max_cache_size.or(default_config.max_cache_size),
                 timeout_ms: init.timeout_ms.unwrap_or(default_config.timeout_ms),
                 retry_config: init.retry_config.unwrap_or(default_config.retry_config),
+                safety_settings: init.safety_settings.unwrap_or(default_config.safety_settings),
+                backend: init.backend.unwrap_or(default_config.backend),
+                base_url: init.base_url.or(default_config.base_url),
+                api_version: init.api_version.or(default_config.api_version),
+                headers: init.headers.unwrap_or(default_config.headers),
+                payload_logging: init.payload_logging.unwrap_or(default_config.payload_logging),
+                fallback_models: init.fallback_models.unwrap_or(default_config.fallback_models),
+                model_aliases: init.model_aliases.unwrap_or(default_config.model_aliases),
+                rate_limits: init.rate_limits.unwrap_or(default_config.rate_limits),
+                rate_limit_mode: init.rate_limit_mode.unwrap_or(default_config.rate_limit_mode),
+                prices: init.prices.unwrap_or(default_config.prices),
+                context_cache: init.context_cache.or(default_config.context_cache),
             },
             None => default_config,
         };
-        
+
         Self {
             id,
-            api_key,
+            api_keys: KeyPool::new(api_keys),
             config,
             store_id,
+            cache: ResponseCache::default(),
+            access_token: None,
+            rate_limiter: RateLimiter::default(),
+            usage: UsageLedger::default(),
+            files: BTreeMap::new(),
+            context_caches: BTreeMap::new(),
         }
     }
 }
@@ -0,0 +1,100 @@
+use crate::types::gemini::UsageMetadata;
+use genai_types::ModelPricing;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Prices of one model in US dollars per million tokens
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_cost_per_million_tokens: f64,
+
+    /// Also applied to thinking tokens, which are billed as output
+    pub output_cost_per_million_tokens: f64,
+
+    /// Price of prompt tokens served from a context cache; the input price if unset
+    #[serde(default)]
+    pub cached_input_cost_per_million_tokens: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Cost in US dollars of the tokens reported for one request
+    pub fn cost(&self, usage: &UsageMetadata) -> f64 {
+        let cached = usage.cached_content_token_count.unwrap_or(0).min(usage.prompt_token_count);
+        let uncached = usage.prompt_token_count - cached;
+        let output = usage.candidates_token_count + usage.thoughts_token_count.unwrap_or(0);
+        let cached_price = self
+            .cached_input_cost_per_million_tokens
+            .unwrap_or(self.input_cost_per_million_tokens);
+
+        (uncached as f64 * self.input_cost_per_million_tokens
+            + cached as f64 * cached_price
+            + output as f64 * self.output_cost_per_million_tokens)
+            / 1_000_000.0
+    }
+}
+
+impl From<ModelPrice> for ModelPricing {
+    fn from(price: ModelPrice) -> Self {
+        ModelPricing {
+            input_cost_per_million_tokens: price.input_cost_per_million_tokens,
+            output_cost_per_million_tokens: price.output_cost_per_million_tokens,
+        }
+    }
+}
+
+/// Running totals for one model
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelUsage {
+    /// Requests that returned usage metadata
+    pub requests: u64,
+    /// Prompt tokens, cached ones included
+    pub input_tokens: u64,
+    /// Answer tokens, thinking excluded
+    pub output_tokens: u64,
+    /// Prompt tokens served from a context cache
+    pub cached_tokens: u64,
+    /// Tokens spent on reasoning by thinking models
+    pub thought_tokens: u64,
+    /// Cost in US dollars; only requests to models with a configured price count
+    pub cost: f64,
+}
+
+impl ModelUsage {
+    fn add(&mut self, other: &ModelUsage) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.thought_tokens += other.thought_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// Token usage and cost per model, accumulated in the actor state
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UsageLedger {
+    pub models: BTreeMap<String, ModelUsage>,
+}
+
+impl UsageLedger {
+    /// Add the usage one request to `model` reported, priced at `price` if known
+    pub fn record(&mut self, model: &str, usage: &UsageMetadata, price: Option<&ModelPrice>) {
+        self.models.entry(model.to_string()).or_default().add(&ModelUsage {
+            requests: 1,
+            input_tokens: usage.prompt_token_count as u64,
+            output_tokens: usage.candidates_token_count as u64,
+            cached_tokens: usage.cached_content_token_count.unwrap_or(0) as u64,
+            thought_tokens: usage.thoughts_token_count.unwrap_or(0) as u64,
+            cost: price.map_or(0.0, |price| price.cost(usage)),
+        });
+    }
+
+    /// Totals over all models
+    pub fn total(&self) -> ModelUsage {
+        let mut total = ModelUsage::default();
+        for usage in self.models.values() {
+            total.add(usage);
+        }
+        total
+    }
+}